@@ -0,0 +1,56 @@
+//! Pushes a capture file through the pipeline and the sniffer as fast as possible and
+//! reports the throughput. Nothing matches the empty achievement list, so every packet
+//! in the file gets decoded.
+//!
+//! `cargo bench --features pcap -- part-001.pcapng gi`
+
+use std::{env, path::PathBuf, sync::mpsc, time::Instant};
+
+use stardb_exporter::{
+    capture::{self, CaptureSession, CaptureSource, FileSource},
+    games::Game,
+};
+
+fn main() -> anyhow::Result<()> {
+    // cargo passes --bench along
+    let mut args = env::args().skip(1).filter(|arg| !arg.starts_with("--"));
+    let Some(path) = args.next().map(PathBuf::from) else {
+        eprintln!("No capture given, run with -- <file> [hsr|gi]");
+        return Ok(());
+    };
+    let game: Game = args.next().as_deref().unwrap_or("hsr").parse()?;
+
+    let port_range = game.achievement_spec()?.port_range;
+    let session = CaptureSession::default();
+    let stats = session.stats().clone();
+
+    let (message_tx, _message_rx) = mpsc::channel();
+    let (device_tx, device_rx) = capture::channel(port_range, stats.clone(), message_tx.clone());
+    let context = capture::Context {
+        port_ranges: vec![port_range],
+        device_tx,
+        message_tx,
+        recording: None,
+        session,
+    };
+
+    // Reads the whole file before the clock starts
+    Box::new(FileSource::new(path)).start(&context)?;
+    drop(context);
+
+    let start = Instant::now();
+    let _ = game.sniff_packets(&[], &device_rx, |_, _, _| true);
+    let seconds = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} packets ({:.1} MB) in {seconds:.3}s: {:.0} packets/s, {:.1} MB/s, {} dropped, {} duplicates",
+        stats.frames(),
+        stats.bytes() as f64 / 1e6,
+        stats.frames() as f64 / seconds,
+        stats.bytes() as f64 / 1e6 / seconds,
+        stats.dropped(),
+        stats.duplicates()
+    );
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use std::{path::PathBuf, sync::mpsc, thread};
 
 use crate::{
-    games::{self, Game},
+    capture::{self, Backend, CaptureSession, DeviceInfo, DeviceSelection, Diagnosis},
+    games::{self, Account, Game},
+    pcapng,
     themes::{self, Theme},
     ui,
 };
@@ -10,11 +12,16 @@ pub enum State {
     #[cfg(not(debug_assertions))]
     OutOfDate(self_update::Status),
     Menu,
+    Settings,
+    /// Devices to pick from for a game, or for game detection
+    Devices(Option<Game>, Vec<DeviceInfo>),
     Login(String, String),
     Waiting(String),
+    Diagnostics(Diagnosis),
     PullMenu,
     Game,
-    Achievements(Vec<u32>),
+    /// Every login seen during the capture and the one shown
+    Achievements(Vec<Account>, usize),
     Pulls(String),
     Error(String),
 }
@@ -27,8 +34,15 @@ pub enum Message {
     Updated(Option<self_update::Status>),
     User(Option<User>),
     Path(PathBuf),
+    Settings(Settings),
+    Capture(CaptureSession),
+    /// Achievements that changed while watching, the game and the account they changed
+    /// for. Updates the results if they're shown
+    Achievements(Game, Vec<Account>, usize),
     Logout,
     Toast(egui_notify::Toast),
+    /// Restarts the exporter with admin rights, after saving what's picked
+    Elevate,
 }
 
 pub struct App {
@@ -40,8 +54,11 @@ pub struct App {
     pub theme: themes::Theme,
     pub user: Option<User>,
     pub paths: Paths,
+    pub settings: Settings,
+    pub capture: Option<CaptureSession>,
     pub account_popup_open: bool,
     pub theme_popup_open: bool,
+    pub elevate: bool,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -57,8 +74,97 @@ pub struct Paths {
     pub gi: Option<PathBuf>,
 }
 
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Devices {
+    pub zzz: DeviceSelection,
+    pub hsr: DeviceSelection,
+    pub gi: DeviceSelection,
+    /// Used when detecting the game
+    pub auto: DeviceSelection,
+}
+
+impl Devices {
+    /// The selection of `game`, or of game detection for `None`
+    pub fn get(&self, game: Option<Game>) -> &DeviceSelection {
+        match game {
+            Some(Game::Hsr) => &self.hsr,
+            Some(Game::Gi) => &self.gi,
+            Some(Game::Zzz) => &self.zzz,
+            None => &self.auto,
+        }
+    }
+
+    pub fn get_mut(&mut self, game: Option<Game>) -> &mut DeviceSelection {
+        match game {
+            Some(Game::Hsr) => &mut self.hsr,
+            Some(Game::Gi) => &mut self.gi,
+            Some(Game::Zzz) => &mut self.zzz,
+            None => &mut self.auto,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Tried first, the other compiled in backends are the fallback
+    pub backend: Backend,
+    /// Devices the pcap backend captures on
+    pub devices: Devices,
+    /// Where the stream backend reads from: `-` for stdin, `tcp://<address>:<port>` to
+    /// listen for a connection, anything else is a named pipe
+    pub stream: String,
+    /// Address the capture helper listens on
+    pub helper: String,
+    /// Local backend the capture helper captures with
+    pub helper_backend: Backend,
+    /// Size at which a capture file rolls over into the next part. 0 disables rollover
+    pub max_capture_file_mb: u32,
+    pub compress_captures: bool,
+    /// Number of capture sessions kept on disk. 0 keeps all of them
+    pub keep_captures: u32,
+    /// Total size of all kept capture sessions. 0 means no limit
+    pub keep_captures_mb: u32,
+    /// Seconds without game traffic before the diagnostics show up. 0 disables them
+    pub watchdog_seconds: u32,
+    /// Keeps captures in memory and only saves them if the export didn't work
+    pub flight_recorder: bool,
+    /// Memory the flight recorder may use, older packets make room for new ones
+    pub flight_recorder_mb: u32,
+    /// Adds every achievement's status, progress and finish time to the copied JSON
+    pub detailed_export: bool,
+    /// Keeps the capture running after the export to pick up achievements finished
+    /// while playing
+    pub keep_watching: bool,
+    /// Syncs to the logged in account whenever an achievement is finished while watching
+    pub auto_sync: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            devices: Devices::default(),
+            stream: "tcp://127.0.0.1:5555".to_string(),
+            helper: capture::HELPER_ADDRESS.to_string(),
+            helper_backend: Backend::default(),
+            max_capture_file_mb: 64,
+            compress_captures: false,
+            keep_captures: 10,
+            keep_captures_mb: 512,
+            watchdog_seconds: 60,
+            flight_recorder: false,
+            flight_recorder_mb: 64,
+            detailed_export: false,
+            keep_watching: false,
+            auto_sync: false,
+        }
+    }
+}
+
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, replay: Option<(Game, PathBuf)>) -> Self {
         egui_extras::install_image_loaders(&cc.egui_ctx);
 
         let mut fonts = egui::FontDefinitions::default();
@@ -98,11 +204,45 @@ impl App {
             .and_then(|s| eframe::get_value(s, "paths"))
             .unwrap_or_default();
 
+        let settings: Settings = cc
+            .storage
+            .and_then(|s| eframe::get_value(s, "settings"))
+            .unwrap_or_default();
+
+        {
+            let settings = settings.clone();
+
+            thread::spawn(move || {
+                let cleaned = pcapng::captures_dir()
+                    .and_then(|captures| pcapng::clean_captures(&captures, &settings));
+                if let Err(e) = cleaned {
+                    tracing::info!("Error cleaning up old captures: {e}");
+                }
+            });
+        }
+
         cc.egui_ctx.set_style(theme.style());
 
         let (message_tx, message_rx) = mpsc::channel();
 
-        update(&message_tx);
+        let (game, state, capture) = match replay {
+            Some((game, path)) => {
+                let session = game.replay(path, &message_tx);
+                (game, State::Waiting("Preparing".to_string()), Some(session))
+            }
+            None => {
+                // pktmon needs admin rights, the copy started with them takes over
+                if settings.backend.needs_elevation() {
+                    tracing::info!("Asking for admin permissions...");
+                    if let Err(e) = capture::restart_elevated() {
+                        tracing::info!("Couldn't get admin permissions: {e}");
+                    }
+                }
+
+                update(&message_tx);
+                (Game::Hsr, State::Waiting("Updating".to_string()), None)
+            }
+        };
 
         if let Some(user) = &user {
             let message_tx = message_tx.clone();
@@ -144,20 +284,37 @@ impl App {
         Self {
             message_tx,
             message_rx,
-            state: State::Waiting("Updating".to_string()),
-            game: games::Game::Hsr,
+            state,
+            game,
             toasts: egui_notify::Toasts::default().with_anchor(egui_notify::Anchor::BottomRight),
             theme,
             user,
             paths,
+            settings,
+            capture,
             account_popup_open: false,
             theme_popup_open: false,
+            elevate: false,
         }
     }
 
     fn message(&mut self, message: Message) {
         match message {
             Message::GoTo(state) => {
+                if let State::Achievements(..) = self.state {
+                    match state {
+                        // A capture thread that started up again, the results stay
+                        State::Waiting(_) => return,
+                        State::Achievements(..) => {}
+                        // Watching for more achievements ends with the results
+                        _ => {
+                            if let Some(capture) = &self.capture {
+                                capture.stop();
+                            }
+                        }
+                    }
+                }
+
                 self.state = state;
             }
             Message::Game(game) => {
@@ -186,6 +343,35 @@ impl App {
                 games::Game::Gi => self.paths.gi = Some(path),
                 games::Game::Zzz => self.paths.zzz = Some(path),
             },
+            Message::Settings(settings) => self.settings = settings,
+            Message::Capture(session) => {
+                // Only one capture at a time
+                if let Some(previous) = self.capture.replace(session) {
+                    previous.stop();
+                }
+            }
+            Message::Achievements(game, accounts, changed) => {
+                // Left over from a capture whose results were left
+                let State::Achievements(_, selected) = self.state else {
+                    return;
+                };
+
+                // Syncing another login would overwrite the account with an alt's
+                if changed == selected
+                    && self.settings.auto_sync
+                    && let Some(user) = &self.user
+                    && let Ok(spec) = game.achievement_spec()
+                {
+                    ui::achievements::sync(
+                        spec.sync_url.clone(),
+                        user.id.clone(),
+                        accounts[changed].finished(),
+                        &self.message_tx,
+                    );
+                }
+
+                self.state = State::Achievements(accounts, selected);
+            }
             Message::Logout => {
                 let Some(user) = &self.user else {
                     return;
@@ -203,6 +389,7 @@ impl App {
             Message::Toast(toast) => {
                 self.toasts.add(toast);
             }
+            Message::Elevate => self.elevate = true,
         }
     }
 }
@@ -212,13 +399,30 @@ impl eframe::App for App {
         eframe::set_value(storage, "user", &self.user);
         eframe::set_value(storage, "theme", &self.theme);
         eframe::set_value(storage, "paths", &self.paths);
+        eframe::set_value(storage, "settings", &self.settings);
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         if let Ok(message) = self.message_rx.try_recv() {
             self.message(message);
         }
 
+        if self.elevate {
+            self.elevate = false;
+
+            // The copy with admin rights starts with the settings picked here
+            if let Some(storage) = frame.storage_mut() {
+                self.save(storage);
+                storage.flush();
+            }
+
+            if let Err(e) = capture::restart_elevated() {
+                self.toasts.add(egui_notify::Toast::error(format!(
+                    "Couldn't get admin permissions: {e}"
+                )));
+            }
+        }
+
         ctx.set_style(self.theme.style());
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -227,7 +431,16 @@ impl eframe::App for App {
             ui.separator();
 
             match &self.state {
-                State::Waiting(s) => ui::waiting::show(ui, s),
+                State::Waiting(s) => {
+                    ui::waiting::show(ui, s);
+
+                    if let Some(session) = &self.capture
+                        && !session.is_stopped()
+                    {
+                        ui::waiting::stats(ui, session.stats());
+                        ui::waiting::cancel(ui, session, &self.message_tx);
+                    }
+                }
                 #[cfg(not(debug_assertions))]
                 State::OutOfDate(status) => {
                     ui::waiting::show(
@@ -239,7 +452,12 @@ impl eframe::App for App {
                 }
                 State::Login(username, password) => ui::login::show(ui, username, password, self),
                 State::Menu => ui::menu::show(ui, self),
-                State::Achievements(achievements) => ui::achievements::show(ui, achievements, self),
+                State::Settings => ui::settings::show(ui, self),
+                State::Devices(game, devices) => ui::devices::show(ui, *game, devices, self),
+                State::Diagnostics(diagnosis) => ui::diagnostics::show(ui, diagnosis, self),
+                State::Achievements(accounts, selected) => {
+                    ui::achievements::show(ui, accounts, *selected, self)
+                }
                 State::Error(s) => ui::error::show(ui, s),
                 State::Game => ui::game::show(ui, self),
                 State::Pulls(url) => ui::pulls::show(ui, url, self),
@@ -255,12 +473,17 @@ impl eframe::App for App {
 fn update(message_tx: &mpsc::Sender<Message>) {
     let message_tx = message_tx.clone();
 
-    let name = if cfg!(all(windows, feature = "pktmon")) {
-        "stardb-exporter-pktmon"
-    } else if cfg!(all(windows, feature = "pcap")) {
-        "stardb-exporter-pcap"
-    } else {
-        "stardb-exporter"
+    // The windows assets are named after the backends they were built with. The one
+    // with both is "windows", "pktmon" would also match the pktmon only assets.
+    let name = match (
+        cfg!(windows),
+        cfg!(feature = "pktmon"),
+        cfg!(feature = "pcap"),
+    ) {
+        (true, true, true) => "stardb-exporter-windows",
+        (true, true, false) => "stardb-exporter-pktmon",
+        (true, false, true) => "stardb-exporter-pcap",
+        _ => "stardb-exporter",
     };
 
     thread::spawn(move || {
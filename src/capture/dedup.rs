@@ -0,0 +1,108 @@
+use std::{
+    collections::{HashMap, VecDeque, hash_map::Entry},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::stats::DeviceStats;
+
+/// Copies of one datagram on different devices arrive within microseconds of each
+/// other, anything older is forgotten
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Recent udp datagrams of all devices. The game's traffic can show up on more than one
+/// device (bridged adapters, a VPN next to the real adapter), and the sniffer has to
+/// see every datagram only once. Whichever copy is kept, the receiver puts it in
+/// capture order.
+#[derive(Default)]
+pub struct Dedup {
+    /// Hash of the datagram, the device it was first passed on from and when
+    seen: HashMap<u64, (Arc<DeviceStats>, Instant)>,
+    expiry: VecDeque<(Instant, u64)>,
+}
+
+impl Dedup {
+    pub fn hash(datagram: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        datagram.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether the datagram was just passed on from another device. Repeats on the
+    /// same device are the game's own retransmissions and stay.
+    pub fn is_duplicate(&mut self, device: &Arc<DeviceStats>, hash: u64) -> bool {
+        let now = Instant::now();
+
+        while let Some((time, hash)) = self.expiry.front()
+            && now.duration_since(*time) > WINDOW
+        {
+            // Unless it was removed and passed on again since
+            if self.seen.get(hash).is_some_and(|(_, seen)| seen == time) {
+                self.seen.remove(hash);
+            }
+            self.expiry.pop_front();
+        }
+
+        self.seen
+            .get(&hash)
+            .is_some_and(|(first, _)| !Arc::ptr_eq(first, device))
+    }
+
+    /// Remembers a datagram passed on from `device`
+    pub fn insert(&mut self, device: &Arc<DeviceStats>, hash: u64) {
+        if let Entry::Vacant(entry) = self.seen.entry(hash) {
+            let now = Instant::now();
+            entry.insert((device.clone(), now));
+            self.expiry.push_back((now, hash));
+        }
+    }
+
+    /// Forgets a datagram `device` couldn't pass on after all
+    pub fn remove(&mut self, device: &Arc<DeviceStats>, hash: u64) {
+        if self
+            .seen
+            .get(&hash)
+            .is_some_and(|(first, _)| Arc::ptr_eq(first, device))
+        {
+            self.seen.remove(&hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::CaptureStats;
+
+    #[test]
+    fn copies_on_other_devices_are_duplicates() {
+        let stats = CaptureStats::default();
+        let (wifi, vpn) = (stats.add_device("wifi"), stats.add_device("vpn"));
+        let mut dedup = Dedup::default();
+        let hash = Dedup::hash(b"datagram");
+
+        assert!(!dedup.is_duplicate(&wifi, hash));
+        dedup.insert(&wifi, hash);
+
+        assert!(dedup.is_duplicate(&vpn, hash));
+        // Retransmissions on the same device
+        assert!(!dedup.is_duplicate(&wifi, hash));
+        assert!(!dedup.is_duplicate(&vpn, Dedup::hash(b"other datagram")));
+    }
+
+    #[test]
+    fn dropped_datagrams_are_not_remembered() {
+        let stats = CaptureStats::default();
+        let (wifi, vpn) = (stats.add_device("wifi"), stats.add_device("vpn"));
+        let mut dedup = Dedup::default();
+        let hash = Dedup::hash(b"datagram");
+
+        // The wifi copy didn't fit into the queue
+        dedup.insert(&wifi, hash);
+        dedup.remove(&vpn, hash);
+        assert!(dedup.is_duplicate(&vpn, hash));
+        dedup.remove(&wifi, hash);
+        assert!(!dedup.is_duplicate(&vpn, hash));
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use super::{CaptureSource, Context, MemorySource};
+use crate::{
+    app::{Message, State},
+    pcapng::{Packets, PcapngReader},
+};
+
+/// Replays a pcapng or pcap file
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl CaptureSource for FileSource {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        let mut reader = Packets::new(PcapngReader::open(&self.path)?);
+
+        tracing::info!("Replaying {}", self.path.display());
+
+        let mut packets = Vec::new();
+        let mut truncated = 0;
+        let mut span: Option<(u64, u64)> = None;
+
+        // Read everything up front, so a broken file fails here and not halfway through
+        while let Some((device, _, packet)) = reader.next_packet()? {
+            if packet.data.len() < packet.original_len as usize {
+                truncated += 1;
+            }
+            // Merged captures aren't always in order
+            let ts = packet.timestamp_ns;
+            span = Some(span.map_or((ts, ts), |(first, last)| (first.min(ts), last.max(ts))));
+
+            tracing::trace!("Packet on device {device} ({} bytes)", packet.data.len());
+
+            packets.push((device, ts, packet.data));
+        }
+
+        let devices = reader
+            .devices()
+            .iter()
+            .enumerate()
+            .map(|(device, interface)| (interface.name_or_numbered(device), interface.link_type))
+            .collect();
+
+        let seconds = span.map_or(0, |(first, last)| last - first) as f64 / 1e9;
+        tracing::info!(
+            "Replaying {} packets ({truncated} truncated) spanning {seconds:.1}s",
+            packets.len()
+        );
+
+        context
+            .message_tx
+            .send(Message::GoTo(State::Waiting("Replaying".to_string())))
+            .unwrap();
+
+        Box::new(MemorySource::new(&self.name(), devices, packets)).start(context)
+    }
+}
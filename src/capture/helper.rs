@@ -0,0 +1,536 @@
+//! Lets a separate, privileged process do the capturing. The helper listens on a local
+//! socket, the GUI connects, says which ports it wants and gets back only the game's
+//! packets, already turned into Ethernet frames.
+//!
+//! Both sides start with [`MAGIC`] and their [`VERSION`], followed by frames of
+//! `kind: u8, len: u32, payload`, all little endian. The GUI sends a [`Request`], the
+//! helper answers with a frame saying whether the capture started and then the packets.
+//!
+//! Only someone who can read the token file gets to capture, and only the game ports
+//! built into the helper.
+
+use std::{
+    fs,
+    io::{self, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use super::{
+    Backend, CaptureSession, CaptureSource, Context, DeviceSelection, PacketReceiver,
+    stream::Stoppable,
+};
+use crate::{
+    app::{Message, Settings, State},
+    games,
+    pcapng::Recording,
+};
+
+/// Where the helper listens unless told otherwise
+pub const HELPER_ADDRESS: &str = "127.0.0.1:5556";
+
+/// Shared secret in the storage dir, created by whichever side starts first
+const TOKEN_FILE: &str = "helper_token";
+
+const MAGIC: &[u8; 4] = b"SDBX";
+/// Bumped whenever the framing or a payload changes
+const VERSION: u16 = 3;
+
+const FRAME_STARTED: u8 = 0;
+const FRAME_ERROR: u8 = 1;
+/// Timestamp in nanoseconds followed by the frame
+const FRAME_PACKET: u8 = 2;
+/// A [`Request`] as JSON
+const FRAME_REQUEST: u8 = 3;
+
+// Anything bigger is a broken stream, not a packet
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// What the GUI asks the helper to capture
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Request {
+    token: String,
+    port_ranges: Vec<(u16, u16)>,
+    backend: Backend,
+    devices: DeviceSelection,
+}
+
+/// Capture source that receives its packets from the helper
+pub struct HelperSource {
+    address: String,
+    backend: Backend,
+    devices: DeviceSelection,
+}
+
+impl HelperSource {
+    pub fn new(address: &str, backend: Backend, devices: DeviceSelection) -> Self {
+        Self {
+            address: address.trim().to_string(),
+            backend,
+            devices,
+        }
+    }
+}
+
+impl CaptureSource for HelperSource {
+    fn name(&self) -> String {
+        "helper".to_string()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        tracing::info!(
+            "Running exporter with the capture helper at {}...",
+            self.address
+        );
+
+        // Fails right away if the helper isn't running or can't capture
+        let connection = connect(&self, context)?;
+
+        let recording = context.recording.clone().and_then(|recording| {
+            let interface_id = recording
+                .lock()
+                .unwrap()
+                .add_interface(1, "helper", Some("Capture helper"))
+                .ok()?;
+            Some((recording, interface_id))
+        });
+
+        let mut context = context.clone();
+        context.device_tx = context.device_tx.for_device("helper", 1);
+
+        thread::spawn(move || capture_helper(&self, connection, recording, &context));
+
+        Ok(())
+    }
+}
+
+fn connect(source: &HelperSource, context: &Context) -> anyhow::Result<TcpStream> {
+    let address = &source.address;
+    let mut stream = TcpStream::connect(address)
+        .map_err(|e| anyhow::anyhow!("Capture helper not reachable at {address}: {e}"))?;
+    // Opening the devices can take a moment, a helper that never answers shouldn't
+    // hang the exporter
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let request = Request {
+        token: load_token(&token_path()?, true)?,
+        port_ranges: context.port_ranges.clone(),
+        backend: source.backend,
+        devices: source.devices.clone(),
+    };
+
+    let mut hello = MAGIC.to_vec();
+    hello.extend_from_slice(&VERSION.to_le_bytes());
+    stream.write_all(&hello)?;
+    write_frame(
+        &mut stream,
+        FRAME_REQUEST,
+        &[&serde_json::to_vec(&request)?],
+    )?;
+
+    let mut header = [0u8; 6];
+    stream.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(anyhow::anyhow!("{address} is not a capture helper"));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(anyhow::anyhow!(
+            "The capture helper speaks protocol version {version}, this exporter {VERSION}. Run both from the same release"
+        ));
+    }
+
+    match read_frame(&mut stream)? {
+        (FRAME_STARTED, message) => tracing::info!("{}", String::from_utf8_lossy(&message)),
+        (FRAME_ERROR, message) => {
+            return Err(anyhow::anyhow!(
+                "Capture helper: {}",
+                String::from_utf8_lossy(&message)
+            ));
+        }
+        (kind, _) => return Err(anyhow::anyhow!("Unexpected helper frame {kind}")),
+    }
+
+    // Wake up regularly to notice a stopped session
+    stream.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+    Ok(stream)
+}
+
+fn capture_helper(
+    source: &HelperSource,
+    mut connection: TcpStream,
+    recording: Option<(Arc<Mutex<Recording>>, u32)>,
+    context: &Context,
+) -> anyhow::Result<()> {
+    let Context {
+        device_tx,
+        message_tx,
+        session,
+        ..
+    } = context;
+
+    while !session.is_stopped() {
+        message_tx
+            .send(Message::Toast({
+                let mut toast = egui_notify::Toast::success("Capture Helper Ready~!".to_string());
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+
+        message_tx
+            .send(Message::GoTo(State::Waiting("Running".to_string())))
+            .unwrap();
+        tracing::info!("Capture Helper Ready~!");
+
+        let mut reader = Stoppable::new(&connection, session);
+
+        let error = loop {
+            let (kind, payload) = match read_frame(&mut reader) {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    break anyhow::anyhow!("Connection closed");
+                }
+                Err(e) => break anyhow::Error::from(e),
+            };
+
+            match kind {
+                FRAME_PACKET if payload.len() >= 8 => {
+                    let ts = u64::from_le_bytes(payload[..8].try_into().unwrap());
+                    let data = &payload[8..];
+                    match device_tx.send(data, ts) {
+                        Ok(true) => {}
+                        Ok(false) => continue,
+                        // The sniffer is done
+                        Err(_) => return Ok(()),
+                    }
+
+                    if let Some((recording, interface_id)) = &recording {
+                        let _ = recording
+                            .lock()
+                            .unwrap()
+                            .write_packet(*interface_id, ts, data);
+                    }
+                }
+                FRAME_ERROR => {
+                    break anyhow::anyhow!("{}", String::from_utf8_lossy(&payload));
+                }
+                kind => tracing::debug!("Skipping helper frame {kind}"),
+            }
+        };
+
+        if session.is_stopped() {
+            break;
+        }
+
+        message_tx
+            .send(Message::Toast({
+                let mut toast =
+                    egui_notify::Toast::error("Capture Helper Error. Reconnecting...".to_string());
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+        tracing::info!("Capture Helper Error: {error}. Reconnecting...");
+
+        // The helper may be restarting, keep trying until it's back
+        connection = loop {
+            if session.is_stopped() {
+                return Ok(());
+            }
+
+            match connect(source, context) {
+                Ok(connection) => break connection,
+                Err(e) => {
+                    tracing::debug!("{e}");
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+        };
+    }
+
+    tracing::info!("Capture helper stopped");
+
+    Ok(())
+}
+
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    reader.read_exact(&mut header)?;
+
+    let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {len} bytes"),
+        ));
+    }
+
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+
+    Ok((header[0], payload))
+}
+
+fn write_frame(writer: &mut impl Write, kind: u8, payload: &[&[u8]]) -> io::Result<()> {
+    let len: usize = payload.iter().map(|p| p.len()).sum();
+
+    writer.write_all(&[kind])?;
+    writer.write_all(&(len as u32).to_le_bytes())?;
+    for part in payload {
+        writer.write_all(part)?;
+    }
+    writer.flush()
+}
+
+/// Runs the helper: serves one GUI at a time, each connection gets its own capture
+/// which stops when the GUI goes away. `token_file` is the app's token, if the helper
+/// runs as another user and can't share its storage dir.
+pub fn run_helper(address: &str, token_file: Option<&Path>) -> anyhow::Result<()> {
+    let token = match token_file {
+        Some(path) => load_token(path, false)?,
+        None => load_token(&token_path()?, true)?,
+    };
+
+    let listener = TcpListener::bind(address)?;
+    tracing::info!("Capture helper listening on {address}");
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::info!("Capture helper couldn't accept a connection: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = serve(stream, &token) {
+            tracing::info!("Capture helper connection ended: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream, token: &str) -> anyhow::Result<()> {
+    tracing::info!("Capture helper connection from {}", stream.peer_addr()?);
+    // Connections are served one after another, a client that never finishes the
+    // handshake mustn't keep everyone else out
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut hello = [0u8; 6];
+    stream.read_exact(&mut hello)?;
+    if &hello[..4] != MAGIC {
+        return Err(anyhow::anyhow!("Not an exporter"));
+    }
+
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    stream.write_all(&header)?;
+
+    let version = u16::from_le_bytes([hello[4], hello[5]]);
+    if version != VERSION {
+        // The exporter reports the mismatch from the header
+        return Err(anyhow::anyhow!(
+            "Exporter speaks protocol version {version}"
+        ));
+    }
+
+    let request = match read_frame(&mut stream)? {
+        (FRAME_REQUEST, request) => serde_json::from_slice::<Request>(&request)?,
+        (kind, _) => return Err(anyhow::anyhow!("Unexpected exporter frame {kind}")),
+    };
+
+    if let Err(e) = check(&request, token) {
+        write_frame(&mut stream, FRAME_ERROR, &[e.to_string().as_bytes()])?;
+        return Err(e);
+    }
+
+    let Request {
+        port_ranges,
+        backend,
+        devices,
+        ..
+    } = request;
+
+    let session = CaptureSession::default();
+    // Nobody looks at the toasts and screens of the capture threads here
+    let (message_tx, message_rx) = mpsc::channel::<Message>();
+    thread::spawn(move || for _ in message_rx {});
+
+    let (device_tx, device_rxs) =
+        super::channels(&port_ranges, session.stats().clone(), message_tx.clone());
+    let context = Context {
+        port_ranges,
+        device_tx,
+        message_tx,
+        recording: None,
+        session: session.clone(),
+    };
+
+    let settings = Settings {
+        backend,
+        ..Settings::default()
+    };
+    let sources = super::live_sources(&settings, &devices);
+    let mut writer = BufWriter::new(stream.try_clone()?);
+
+    match super::start(sources, &context) {
+        Ok(name) => {
+            let message = format!("Capture helper capturing with {name}");
+            write_frame(&mut writer, FRAME_STARTED, &[message.as_bytes()])?;
+        }
+        Err(e) => {
+            write_frame(&mut writer, FRAME_ERROR, &[e.to_string().as_bytes()])?;
+            return Err(e);
+        }
+    }
+    drop(context);
+
+    // The exporter doesn't send anything after the hello, so a finished read means
+    // it went away
+    stream.set_read_timeout(None)?;
+    {
+        let session = session.clone();
+        thread::spawn(move || {
+            let _ = stream.read(&mut [0u8; 1]);
+            session.stop();
+        });
+    }
+
+    // One queue per port range, they all end up on the same connection
+    let writer = Mutex::new(writer);
+    let result = thread::scope(|scope| {
+        let forwarders: Vec<_> = device_rxs
+            .into_iter()
+            .map(|device_rx| {
+                let (writer, session) = (&writer, &session);
+                scope.spawn(move || forward(&device_rx, writer, session))
+            })
+            .collect();
+
+        forwarders
+            .into_iter()
+            .try_for_each(|forwarder| forwarder.join().unwrap())
+    });
+
+    session.stats().log();
+
+    Ok(result?)
+}
+
+/// Only the token's owner may capture, and only the traffic of the games
+fn check(request: &Request, token: &str) -> anyhow::Result<()> {
+    if request.token != token {
+        return Err(anyhow::anyhow!(
+            "Wrong token. The app and the helper need to use the same {TOKEN_FILE} file"
+        ));
+    }
+
+    let allowed = games::builtin_port_ranges();
+    if let Some((from, to)) = request.port_ranges.iter().find(|r| !allowed.contains(r)) {
+        return Err(anyhow::anyhow!(
+            "Ports {from}-{to} aren't built in, the helper only captures the games' ports"
+        ));
+    }
+
+    if !request.backend.is_local() {
+        return Err(anyhow::anyhow!(
+            "The helper can't capture with {}",
+            request.backend.name()
+        ));
+    }
+
+    Ok(())
+}
+
+fn token_path() -> anyhow::Result<PathBuf> {
+    let mut path =
+        anyhow::Context::context(eframe::storage_dir(crate::APP_ID), "Storage dir not found")?;
+    path.push(TOKEN_FILE);
+
+    Ok(path)
+}
+
+/// Reads the token. With `create`, a missing one is made up and saved so only the
+/// current user can read it.
+fn load_token(path: &Path, create: bool) -> anyhow::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) => return Ok(token.trim().to_string()),
+        Err(e) if !create || e.kind() != io::ErrorKind::NotFound => {
+            return Err(anyhow::anyhow!("Can't read {}: {e}", path.display()));
+        }
+        Err(_) => {}
+    }
+
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    match options.open(path) {
+        Ok(mut file) => file.write_all(token.as_bytes())?,
+        // The other side was quicker
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return load_token(path, false),
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(token)
+}
+
+fn forward(
+    device_rx: &PacketReceiver,
+    writer: &Mutex<BufWriter<TcpStream>>,
+    session: &CaptureSession,
+) -> io::Result<()> {
+    while let Some((ts, data)) = device_rx.recv_timed() {
+        let result = write_frame(
+            &mut *writer.lock().unwrap(),
+            FRAME_PACKET,
+            &[&ts.to_le_bytes(), &data],
+        );
+        if let Err(e) = result {
+            session.stop();
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(token: &str, port_ranges: Vec<(u16, u16)>, backend: Backend) -> Request {
+        Request {
+            token: token.to_string(),
+            port_ranges,
+            backend,
+            devices: DeviceSelection::default(),
+        }
+    }
+
+    #[test]
+    fn only_game_ports_are_captured_for_the_token_owner() {
+        let games = games::builtin_port_ranges();
+        let everything = vec![(1, 65535)];
+
+        assert!(check(&request("secret", games.clone(), Backend::Pcap), "secret").is_ok());
+        assert!(check(&request("guess", games.clone(), Backend::Pcap), "secret").is_err());
+        assert!(check(&request("secret", everything, Backend::Pcap), "secret").is_err());
+        assert!(check(&request("secret", games, Backend::Stream), "secret").is_err());
+    }
+}
@@ -0,0 +1,512 @@
+//! Turns every supported datalink type into plain Ethernet II frames, the only thing
+//! the sniffers understand. The anonymizer uses the same parsing to find the addresses
+//! it rewrites.
+
+use std::ops::Range;
+
+const LINKTYPE_NULL: u16 = 0;
+pub const LINKTYPE_ETHERNET: u16 = 1;
+pub const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_IEEE802_11: u16 = 105;
+const LINKTYPE_LOOP: u16 = 108;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_IEEE802_11_RADIOTAP: u16 = 127;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+pub const IPPROTO_UDP: u8 = 17;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+
+pub fn is_supported(link_type: u16) -> bool {
+    matches!(
+        link_type,
+        LINKTYPE_NULL
+            | LINKTYPE_ETHERNET
+            | LINKTYPE_RAW
+            | LINKTYPE_IEEE802_11
+            | LINKTYPE_LOOP
+            | LINKTYPE_LINUX_SLL
+            | LINKTYPE_IEEE802_11_RADIOTAP
+            | LINKTYPE_IPV4
+            | LINKTYPE_IPV6
+            | LINKTYPE_LINUX_SLL2
+    )
+}
+
+/// Link type of a packet from a capture that mixes Ethernet frames with bare ip
+/// packets, like pktmon, which passes on whatever each adapter has. Only udp gets
+/// through its filters, so the reading that finds a udp header is the right one.
+pub fn ethernet_or_raw(data: &[u8]) -> u16 {
+    if udp_ports(LINKTYPE_ETHERNET, data).is_none() && udp_ports(LINKTYPE_RAW, data).is_some() {
+        LINKTYPE_RAW
+    } else {
+        LINKTYPE_ETHERNET
+    }
+}
+
+/// An untagged Ethernet frame carrying the same ip packet. `None` for frames without
+/// an ip packet, encrypted 802.11 frames and unsupported link types.
+pub fn normalize(link_type: u16, mut data: Vec<u8>) -> Option<Vec<u8>> {
+    let (ethertype, header_len, trailer_len) = locate(link_type, &data)?;
+
+    // Nothing to do for the common case
+    if link_type == LINKTYPE_ETHERNET && header_len == ETHERNET_HEADER_LEN {
+        return Some(data);
+    }
+
+    if ethertype != ETHERTYPE_IPV4 && ethertype != ETHERTYPE_IPV6 {
+        return None;
+    }
+
+    data.truncate(data.len() - trailer_len);
+
+    let mut header = [0; ETHERNET_HEADER_LEN];
+    header[12..].copy_from_slice(&ethertype.to_be_bytes());
+    data.splice(..header_len, header);
+
+    Some(data)
+}
+
+/// Ethertype of the ip packet in a frame and where it is, between the link layer header
+/// and trailer
+pub fn ip_packet(link_type: u16, data: &[u8]) -> Option<(u16, Range<usize>)> {
+    let (ethertype, header_len, trailer_len) = locate(link_type, data)?;
+
+    if ethertype != ETHERTYPE_IPV4 && ethertype != ETHERTYPE_IPV6 {
+        return None;
+    }
+
+    Some((ethertype, header_len..data.len() - trailer_len))
+}
+
+/// Offsets of the 6 byte hardware addresses in the link layer header
+pub fn hardware_addresses(link_type: u16, data: &[u8]) -> Vec<usize> {
+    let addresses = match link_type {
+        LINKTYPE_ETHERNET => vec![0, 6],
+        // Only if the address type is Ethernet
+        LINKTYPE_LINUX_SLL if be16(data, 4) == Some(6) => vec![6],
+        LINKTYPE_LINUX_SLL2 if data.get(11) == Some(&6) => vec![12],
+        LINKTYPE_IEEE802_11 => ieee802_11_addresses(data, 0),
+        LINKTYPE_IEEE802_11_RADIOTAP => match radiotap(data) {
+            Some((radiotap_len, _)) => ieee802_11_addresses(data, radiotap_len),
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    addresses
+        .into_iter()
+        .filter(|offset| offset + 6 <= data.len())
+        .collect()
+}
+
+/// Recomputes the checksum at the end of frames that have one, after they were changed
+pub fn update_fcs(link_type: u16, data: &mut [u8]) {
+    if link_type != LINKTYPE_IEEE802_11_RADIOTAP {
+        return;
+    }
+
+    let Some((radiotap_len, true)) = radiotap(data) else {
+        return;
+    };
+    let Some(end) = data.len().checked_sub(4).filter(|end| *end >= radiotap_len) else {
+        return;
+    };
+
+    let mut crc = flate2::Crc::new();
+    crc.update(&data[radiotap_len..end]);
+    data[end..].copy_from_slice(&crc.sum().to_le_bytes());
+}
+
+/// Source and destination port of a udp frame
+pub fn udp_ports(link_type: u16, data: &[u8]) -> Option<(u16, u16)> {
+    let udp = udp(link_type, data)?;
+    Some((be16(udp, 0)?, be16(udp, 2)?))
+}
+
+/// Udp header and payload, without link layer padding, which differs between devices
+pub fn udp(link_type: u16, data: &[u8]) -> Option<&[u8]> {
+    let (ethertype, header_len, _) = locate(link_type, data)?;
+    let ip = data.get(header_len..)?;
+
+    let udp = match ethertype {
+        ETHERTYPE_IPV4 => {
+            let header_len = (*ip.first()? & 0x0f) as usize * 4;
+            let fragment_offset = be16(ip, 6)? & 0x1fff;
+
+            if *ip.get(9)? != IPPROTO_UDP || fragment_offset != 0 {
+                return None;
+            }
+
+            ip.get(header_len..)?
+        }
+        ETHERTYPE_IPV6 => {
+            if *ip.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+
+            ip.get(40..)?
+        }
+        _ => return None,
+    };
+
+    // Truncated frames are shorter than the length says
+    let len = be16(udp, 4)? as usize;
+    if len < 8 {
+        return Some(udp);
+    }
+    udp.get(..len.min(udp.len()))
+}
+
+/// Ethertype of the payload and the length of the link layer header and trailer
+fn locate(link_type: u16, data: &[u8]) -> Option<(u16, usize, usize)> {
+    let (ethertype, header_len) = match link_type {
+        LINKTYPE_ETHERNET => ethernet(data)?,
+        LINKTYPE_LINUX_SLL => (be16(data, 14)?, 16),
+        LINKTYPE_LINUX_SLL2 => {
+            if data.len() < 20 {
+                return None;
+            }
+            (be16(data, 0)?, 20)
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => (ip_version(data, 0)?, 0),
+        // The 4 byte address family is in host byte order for NULL, so go by the ip header
+        LINKTYPE_NULL | LINKTYPE_LOOP => (ip_version(data, 4)?, 4),
+        LINKTYPE_IEEE802_11 => ieee802_11(data, 0)?,
+        LINKTYPE_IEEE802_11_RADIOTAP => {
+            let (radiotap_len, fcs) = radiotap(data)?;
+            let (ethertype, header_len) = ieee802_11(data, radiotap_len)?;
+            let trailer_len = if fcs { 4 } else { 0 };
+
+            if header_len + trailer_len > data.len() {
+                return None;
+            }
+            return Some((ethertype, header_len, trailer_len));
+        }
+        _ => return None,
+    };
+
+    Some((ethertype, header_len, 0))
+}
+
+/// Ethertype and header length, skipping any number of VLAN tags
+fn ethernet(data: &[u8]) -> Option<(u16, usize)> {
+    let mut offset = 12;
+
+    loop {
+        let ethertype = be16(data, offset)?;
+
+        if ethertype != ETHERTYPE_VLAN && ethertype != ETHERTYPE_QINQ {
+            return Some((ethertype, offset + 2));
+        }
+
+        offset += 4;
+    }
+}
+
+fn ip_version(data: &[u8], offset: usize) -> Option<u16> {
+    match data.get(offset)? >> 4 {
+        4 => Some(ETHERTYPE_IPV4),
+        6 => Some(ETHERTYPE_IPV6),
+        _ => None,
+    }
+}
+
+/// Length of the radiotap header and whether the frame ends with a checksum
+fn radiotap(data: &[u8]) -> Option<(usize, bool)> {
+    let header_len = u16::from_le_bytes([*data.get(2)?, *data.get(3)?]) as usize;
+    if header_len > data.len() {
+        return None;
+    }
+
+    let present = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?);
+
+    // More present words follow as long as bit 31 is set
+    let mut offset = 8;
+    let mut word = present;
+    while word & (1 << 31) != 0 {
+        word = u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?);
+        offset += 4;
+    }
+
+    // The flags field comes right after the 8 byte aligned TSFT field, if that is present
+    let mut fcs = false;
+    if present & 0b10 != 0 {
+        if present & 0b01 != 0 {
+            offset = offset.next_multiple_of(8) + 8;
+        }
+        fcs = data.get(offset).is_some_and(|flags| flags & 0x10 != 0);
+    }
+
+    Some((header_len, fcs))
+}
+
+/// Ethertype and the length of the 802.11 and LLC/SNAP headers starting at `offset`
+fn ieee802_11(data: &[u8], offset: usize) -> Option<(u16, usize)> {
+    let frame = data.get(offset..)?;
+    let frame_control = *frame.first()?;
+    let flags = *frame.get(1)?;
+
+    let frame_type = (frame_control >> 2) & 0b11;
+    let subtype = frame_control >> 4;
+
+    // Only data frames that actually carry data and aren't encrypted
+    if frame_type != 2 || subtype & 0b0100 != 0 || flags & 0x40 != 0 {
+        return None;
+    }
+
+    let mut header_len = 24;
+    // To and from the distribution system adds a fourth address
+    if flags & 0b11 == 0b11 {
+        header_len += 6;
+    }
+    // QoS data has a QoS control field, and with the order flag a HT control field
+    if subtype & 0b1000 != 0 {
+        header_len += 2;
+        if flags & 0x80 != 0 {
+            header_len += 4;
+        }
+    }
+
+    let llc = frame.get(header_len..header_len + 8)?;
+    if llc[..6] != [0xaa, 0xaa, 0x03, 0, 0, 0] {
+        return None;
+    }
+
+    Some((be16(llc, 6)?, offset + header_len + 8))
+}
+
+/// Offsets of the three or four addresses of the 802.11 frame starting at `offset`
+fn ieee802_11_addresses(data: &[u8], offset: usize) -> Vec<usize> {
+    let Some(flags) = data.get(offset + 1) else {
+        return Vec::new();
+    };
+    let count = if flags & 0b11 == 0b11 { 4 } else { 3 };
+
+    [4, 10, 16, 24][..count]
+        .iter()
+        .map(|address| offset + address)
+        .collect()
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+    ]))
+}
+
+/// Frames for the tests of the capture pipeline
+#[cfg(test)]
+pub mod fixtures {
+    use super::{ETHERTYPE_IPV4, IPPROTO_UDP};
+
+    pub fn udp(port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = [port.to_be_bytes(), 50000u16.to_be_bytes()].concat();
+        udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        udp
+    }
+
+    pub fn ipv4(port: u16, payload: &[u8]) -> Vec<u8> {
+        let udp = udp(port, payload);
+        let mut ip = vec![0x45, 0];
+        ip.extend_from_slice(&(20 + udp.len() as u16).to_be_bytes());
+        ip.extend_from_slice(&[0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        ip.extend(udp);
+        ip
+    }
+
+    pub fn ipv6(port: u16, payload: &[u8]) -> Vec<u8> {
+        let udp = udp(port, payload);
+        let mut ip = vec![0x60, 0, 0, 0];
+        ip.extend_from_slice(&(udp.len() as u16).to_be_bytes());
+        ip.extend_from_slice(&[IPPROTO_UDP, 64]);
+        ip.extend_from_slice(&[0xfd; 32]);
+        ip.extend(udp);
+        ip
+    }
+
+    pub fn ethernet(ethertype: u16, ip: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(ip);
+        frame
+    }
+
+    /// Ethernet, IPv4 and udp from `port`
+    pub fn frame(port: u16, payload: &[u8]) -> Vec<u8> {
+        ethernet(ETHERTYPE_IPV4, &ipv4(port, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{ethernet, ipv4, ipv6};
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"login";
+
+    /// Data frame from a station to the access point, with LLC/SNAP
+    fn ieee802_11(ip: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x08, 0x01, 0, 0];
+        frame.extend_from_slice(&[0x12; 18]);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&[0xaa, 0xaa, 0x03, 0, 0, 0, 0x08, 0x00]);
+        frame.extend_from_slice(ip);
+        frame
+    }
+
+    /// Every supported link type with a sample frame carrying the IPv4 or IPv6 login
+    fn samples() -> Vec<(&'static str, u16, Vec<u8>, Vec<u8>)> {
+        let (v4, v6) = (ipv4(22101, PAYLOAD), ipv6(22101, PAYLOAD));
+
+        let mut vlan = vec![0x34; 12];
+        vlan.extend_from_slice(&[0x81, 0x00, 0, 7, 0x88, 0xa8, 0, 8, 0x08, 0x00]);
+        vlan.extend_from_slice(&v4);
+
+        // AF_INET in host byte order, and AF_INET6 in network byte order
+        let null = [&2u32.to_le_bytes()[..], &v4].concat();
+        let loopback = [&24u32.to_be_bytes()[..], &v6].concat();
+
+        let mut sll = vec![0, 0, 0, 1, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0, 0x86, 0xdd];
+        sll.extend_from_slice(&v6);
+
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 3, 0, 1, 0, 6];
+        sll2.extend_from_slice(&[1, 2, 3, 4, 5, 6, 0, 0]);
+        sll2.extend_from_slice(&v4);
+
+        // QoS data between two access points, with all four addresses
+        let mut wds = vec![0x88, 0x03, 0, 0];
+        wds.extend_from_slice(&[0x56; 18]);
+        wds.extend_from_slice(&[0, 0]);
+        wds.extend_from_slice(&[0x78; 6]);
+        wds.extend_from_slice(&[0, 0]);
+        wds.extend_from_slice(&[0xaa, 0xaa, 0x03, 0, 0, 0, 0x86, 0xdd]);
+        wds.extend_from_slice(&v6);
+
+        // Radiotap header with only the flags field, saying there's a checksum at the end
+        let mut radiotap = vec![0, 0, 9, 0, 0b10, 0, 0, 0, 0x10];
+        radiotap.extend(ieee802_11(&v4));
+        radiotap.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+
+        vec![
+            (
+                "ethernet",
+                LINKTYPE_ETHERNET,
+                ethernet(ETHERTYPE_IPV4, &v4),
+                v4.clone(),
+            ),
+            ("vlan", LINKTYPE_ETHERNET, vlan, v4.clone()),
+            ("null", LINKTYPE_NULL, null, v4.clone()),
+            ("loop", LINKTYPE_LOOP, loopback, v6.clone()),
+            ("raw", LINKTYPE_RAW, v6.clone(), v6.clone()),
+            ("ipv4", LINKTYPE_IPV4, v4.clone(), v4.clone()),
+            ("ipv6", LINKTYPE_IPV6, v6.clone(), v6.clone()),
+            ("sll", LINKTYPE_LINUX_SLL, sll, v6.clone()),
+            ("sll2", LINKTYPE_LINUX_SLL2, sll2, v4.clone()),
+            ("802.11", LINKTYPE_IEEE802_11, ieee802_11(&v4), v4.clone()),
+            ("802.11 wds", LINKTYPE_IEEE802_11, wds, v6),
+            ("radiotap", LINKTYPE_IEEE802_11_RADIOTAP, radiotap, v4),
+        ]
+    }
+
+    #[test]
+    fn normalizes_every_link_type_to_ethernet() {
+        for (name, link_type, frame, ip) in samples() {
+            assert!(is_supported(link_type), "{name}");
+
+            let ethertype = if ip[0] >> 4 == 4 {
+                ETHERTYPE_IPV4
+            } else {
+                ETHERTYPE_IPV6
+            };
+
+            assert_eq!(udp_ports(link_type, &frame), Some((22101, 50000)), "{name}");
+            assert_eq!(
+                ip_packet(link_type, &frame).map(|(e, range)| (e, frame[range].to_vec())),
+                Some((ethertype, ip.clone())),
+                "{name}"
+            );
+            assert_eq!(
+                normalize(link_type, frame),
+                Some(ethernet(ethertype, &ip)),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn tells_ethernet_frames_from_bare_ip_packets() {
+        let (v4, v6) = (ipv4(22101, PAYLOAD), ipv6(22101, PAYLOAD));
+
+        assert_eq!(
+            ethernet_or_raw(&ethernet(ETHERTYPE_IPV4, &v4)),
+            LINKTYPE_ETHERNET
+        );
+        assert_eq!(
+            ethernet_or_raw(&ethernet(ETHERTYPE_IPV6, &v6)),
+            LINKTYPE_ETHERNET
+        );
+        assert_eq!(ethernet_or_raw(&v4), LINKTYPE_RAW);
+        assert_eq!(ethernet_or_raw(&v6), LINKTYPE_RAW);
+    }
+
+    #[test]
+    fn finds_hardware_addresses() {
+        let addresses: Vec<_> = samples()
+            .into_iter()
+            .map(|(name, link_type, frame, _)| (name, hardware_addresses(link_type, &frame)))
+            .collect();
+
+        assert_eq!(
+            addresses,
+            [
+                ("ethernet", vec![0, 6]),
+                ("vlan", vec![0, 6]),
+                ("null", vec![]),
+                ("loop", vec![]),
+                ("raw", vec![]),
+                ("ipv4", vec![]),
+                ("ipv6", vec![]),
+                ("sll", vec![6]),
+                ("sll2", vec![12]),
+                ("802.11", vec![4, 10, 16]),
+                ("802.11 wds", vec![4, 10, 16, 24]),
+                ("radiotap", vec![13, 19, 25]),
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_the_radiotap_checksum() {
+        let (_, link_type, mut frame, _) = samples().pop().unwrap();
+        update_fcs(link_type, &mut frame);
+
+        let end = frame.len() - 4;
+        let mut crc = flate2::Crc::new();
+        crc.update(&frame[9..end]);
+        assert_eq!(frame[end..], crc.sum().to_le_bytes());
+    }
+
+    #[test]
+    fn skips_frames_without_ip() {
+        let mut encrypted = ieee802_11(&ipv4(22101, PAYLOAD));
+        encrypted[1] |= 0x40;
+        assert_eq!(normalize(LINKTYPE_IEEE802_11, encrypted), None);
+
+        let arp = ethernet(0x0806, &[0; 28]);
+        assert_eq!(ip_packet(LINKTYPE_ETHERNET, &arp), None);
+        assert_eq!(udp_ports(LINKTYPE_ETHERNET, &arp), None);
+
+        assert_eq!(normalize(147, ipv4(22101, PAYLOAD)), None);
+    }
+}
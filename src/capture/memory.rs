@@ -0,0 +1,55 @@
+use std::thread;
+
+use super::{CaptureSource, Context};
+
+/// Feeds a fixed list of packets to the sniffer, then ends the capture
+pub struct MemorySource {
+    name: String,
+    /// Name and link type
+    devices: Vec<(String, u16)>,
+    /// Index into `devices`, capture timestamp and frame
+    packets: Vec<(usize, u64, Vec<u8>)>,
+}
+
+impl MemorySource {
+    pub fn new(
+        name: &str,
+        devices: Vec<(String, u16)>,
+        packets: Vec<(usize, u64, Vec<u8>)>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            devices,
+            packets,
+        }
+    }
+}
+
+impl CaptureSource for MemorySource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        let device_txs: Vec<_> = self
+            .devices
+            .iter()
+            .map(|(name, link_type)| context.device_tx.for_device(name, *link_type))
+            .collect();
+        let session = context.session.clone();
+
+        thread::spawn(move || {
+            for (device, timestamp_ns, packet) in self.packets {
+                if session.is_stopped()
+                    || device_txs[device]
+                        .send_blocking(packet, timestamp_ns)
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+mod dedup;
+mod file;
+mod helper;
+pub mod link;
+mod memory;
+#[cfg(feature = "pcap")]
+mod pcap;
+mod pipeline;
+#[cfg(feature = "pktmon")]
+mod pktmon;
+mod stats;
+mod stream;
+mod watchdog;
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+
+pub use file::FileSource;
+pub use helper::{HELPER_ADDRESS, HelperSource, run_helper};
+pub use memory::MemorySource;
+pub use pipeline::{PacketReceiver, PacketSender, channel, channels};
+pub use stats::CaptureStats;
+pub use stream::StreamSource;
+pub use watchdog::Diagnosis;
+
+use crate::{
+    app::{Message, Settings},
+    games::Game,
+    pcapng::Recording,
+};
+
+#[cfg(not(any(feature = "pktmon", feature = "pcap")))]
+compile_error!("at least one of the features \"pktmon\" or \"pcap\" must be enabled");
+
+/// Everything a source needs to hand packets to the sniffer
+#[derive(Clone)]
+pub struct Context {
+    /// Ports of every game that's sniffed for
+    pub port_ranges: Vec<(u16, u16)>,
+    pub device_tx: PacketSender,
+    pub message_tx: mpsc::Sender<Message>,
+    pub recording: Option<Arc<Mutex<Recording>>>,
+    pub session: CaptureSession,
+}
+
+/// Handle to a running capture. Stopping it ends every capture thread, which in turn
+/// closes the recording once the last one is gone.
+#[derive(Clone, Default)]
+pub struct CaptureSession {
+    stopped: Arc<AtomicBool>,
+    claimed: Arc<AtomicBool>,
+    /// Set while the watchdog waits for game traffic. Sources then let all udp through,
+    /// so the diagnosis can tell what else arrives.
+    diagnosing: Arc<AtomicBool>,
+    /// Set by "Keep waiting" to start the watchdog's timer over
+    rearm: Arc<AtomicBool>,
+    stats: Arc<CaptureStats>,
+}
+
+impl CaptureSession {
+    pub fn stats(&self) -> &Arc<CaptureStats> {
+        &self.stats
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Says whether the caller is the first to report a result, without stopping. Of
+    /// several sniffers, only that one gets to show it.
+    pub fn claim(&self) -> bool {
+        !self.claimed.swap(true, Ordering::Relaxed) && !self.is_stopped()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    pub fn is_diagnosing(&self) -> bool {
+        self.diagnosing.load(Ordering::Relaxed)
+    }
+
+    fn set_diagnosing(&self, diagnosing: bool) {
+        self.diagnosing.store(diagnosing, Ordering::Relaxed);
+    }
+
+    /// Gives the game more time before the diagnostics show up again
+    pub fn keep_waiting(&self) {
+        self.rearm.store(true, Ordering::Relaxed);
+    }
+
+    fn take_rearm(&self) -> bool {
+        self.rearm.swap(false, Ordering::Relaxed)
+    }
+}
+
+pub trait CaptureSource: Send {
+    fn name(&self) -> String;
+
+    /// Sets up the capture and spawns the threads feeding `context.device_tx`.
+    /// An error means this source can't be used and the next one should be tried.
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()>;
+}
+
+/// Which devices to capture on, remembered per game
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DeviceSelection {
+    /// Device names picked by the user. `None` picks every connected device with an address
+    pub devices: Option<Vec<String>>,
+    pub include_loopback: bool,
+}
+
+impl DeviceSelection {
+    pub fn uses(&self, device: &DeviceInfo) -> bool {
+        if device.loopback && !self.include_loopback {
+            return false;
+        }
+
+        match &self.devices {
+            Some(names) => names.contains(&device.name),
+            None => device.auto,
+        }
+    }
+}
+
+/// A network device as shown in the device picker
+#[derive(Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub addresses: Vec<String>,
+    pub flags: Vec<String>,
+    pub loopback: bool,
+    /// Whether the automatic selection would pick this device
+    pub auto: bool,
+}
+
+/// Every device pcap can see
+pub fn pcap_devices() -> anyhow::Result<Vec<DeviceInfo>> {
+    #[cfg(feature = "pcap")]
+    {
+        self::pcap::device_infos()
+    }
+    #[cfg(not(feature = "pcap"))]
+    {
+        Err(anyhow::anyhow!("This build has no pcap support"))
+    }
+}
+
+/// Starts a copy of the exporter with the admin rights pktmon needs and exits
+pub fn restart_elevated() -> anyhow::Result<()> {
+    #[cfg(feature = "pktmon")]
+    {
+        self::pktmon::restart_elevated()
+    }
+    #[cfg(not(feature = "pktmon"))]
+    {
+        Err(anyhow::anyhow!("This build has no pktmon support"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Backend {
+    Pcap,
+    Pktmon,
+    /// A pcap or pcapng stream from a capture on another machine
+    Stream,
+    /// The capture helper, a privileged copy of the exporter doing the capturing
+    Helper,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if Backend::Pktmon.is_available() {
+            Backend::Pktmon
+        } else {
+            Backend::Pcap
+        }
+    }
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [
+        Backend::Pcap,
+        Backend::Pktmon,
+        Backend::Stream,
+        Backend::Helper,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Pcap => "pcap",
+            Backend::Pktmon => "pktmon",
+            Backend::Stream => "stream",
+            Backend::Helper => "helper",
+        }
+    }
+
+    /// Whether this backend was compiled in
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Pcap => cfg!(feature = "pcap"),
+            Backend::Pktmon => cfg!(feature = "pktmon"),
+            Backend::Stream | Backend::Helper => true,
+        }
+    }
+
+    /// Whether capturing with this backend needs a restart with admin rights first
+    pub fn needs_elevation(self) -> bool {
+        #[cfg(feature = "pktmon")]
+        {
+            self == Backend::Pktmon && !self::pktmon::is_elevated()
+        }
+        #[cfg(not(feature = "pktmon"))]
+        {
+            false
+        }
+    }
+
+    /// Whether this backend captures on this machine's devices itself
+    pub fn is_local(self) -> bool {
+        matches!(self, Backend::Pcap | Backend::Pktmon)
+    }
+
+    fn source(
+        self,
+        settings: &Settings,
+        selection: &DeviceSelection,
+    ) -> Option<Box<dyn CaptureSource>> {
+        match self {
+            Backend::Pcap => {
+                #[cfg(feature = "pcap")]
+                {
+                    Some(Box::new(self::pcap::PcapSource::new(selection.clone())))
+                }
+                #[cfg(not(feature = "pcap"))]
+                {
+                    let _ = selection;
+                    None
+                }
+            }
+            Backend::Pktmon => {
+                #[cfg(feature = "pktmon")]
+                {
+                    Some(Box::new(self::pktmon::PktmonSource))
+                }
+                #[cfg(not(feature = "pktmon"))]
+                {
+                    None
+                }
+            }
+            Backend::Stream => Some(Box::new(StreamSource::new(&settings.stream))),
+            Backend::Helper => Some(Box::new(HelperSource::new(
+                &settings.helper,
+                settings.helper_backend,
+                selection.clone(),
+            ))),
+        }
+    }
+}
+
+/// The preferred backend first, followed by every other compiled in backend as fallback.
+/// Only local backends fall back to each other: a stream or the helper are picked on
+/// purpose and capturing locally instead would need the privileges they avoid.
+pub fn live_sources(
+    settings: &Settings,
+    selection: &DeviceSelection,
+) -> Vec<Box<dyn CaptureSource>> {
+    let preferred = settings.backend;
+
+    std::iter::once(preferred)
+        .chain(
+            Backend::ALL
+                .into_iter()
+                .filter(|b| *b != preferred && b.is_local() && preferred.is_local()),
+        )
+        .filter_map(|b| b.source(settings, selection))
+        .collect()
+}
+
+/// Starts the first source that works and returns its name
+pub fn start(sources: Vec<Box<dyn CaptureSource>>, context: &Context) -> anyhow::Result<String> {
+    let mut errors = Vec::new();
+
+    for source in sources {
+        let name = source.name();
+
+        if !errors.is_empty() {
+            context
+                .message_tx
+                .send(Message::Toast(egui_notify::Toast::info(format!(
+                    "Falling back to {name}"
+                ))))
+                .unwrap();
+        }
+
+        match source.start(context) {
+            Ok(()) => {
+                tracing::info!("Capturing with {name}");
+                return Ok(name);
+            }
+            Err(e) => {
+                tracing::info!("Couldn't start {name}: {e}");
+                errors.push(format!("{name}: {e}"));
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "No capture backend could be started\n{}",
+        errors.join("\n")
+    ))
+}
+
+/// Warns about a capture that doesn't see any game traffic. `game` is `None` when the
+/// game is detected automatically.
+pub fn watch(context: &Context, seconds: u32, source: String, game: Option<Game>) {
+    watchdog::spawn(
+        context.session.clone(),
+        seconds,
+        context.port_ranges.clone(),
+        source,
+        game,
+        context.message_tx.clone(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::link::fixtures::frame;
+
+    /// Can't be started, like pcap without Npcap
+    struct BrokenSource;
+
+    impl CaptureSource for BrokenSource {
+        fn name(&self) -> String {
+            "broken".to_string()
+        }
+
+        fn start(self: Box<Self>, _context: &Context) -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("Npcap is missing"))
+        }
+    }
+
+    #[test]
+    fn falls_back_to_a_working_source() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let session = CaptureSession::default();
+        let port_ranges = vec![(22101, 22102), (23301, 23302)];
+        let (device_tx, device_rxs) =
+            channels(&port_ranges, session.stats().clone(), message_tx.clone());
+        let context = Context {
+            port_ranges,
+            device_tx,
+            message_tx,
+            recording: None,
+            session: session.clone(),
+        };
+
+        let (gi, hsr, dns) = (frame(22101, b"gi"), frame(23301, b"hsr"), frame(53, b"dns"));
+        let memory = MemorySource::new(
+            "memory",
+            vec![("wifi".to_string(), 1)],
+            vec![(0, 1, gi.clone()), (0, 2, dns), (0, 3, hsr.clone())],
+        );
+
+        let name = start(vec![Box::new(BrokenSource), Box::new(memory)], &context).unwrap();
+        assert_eq!(name, "memory");
+        assert!(matches!(message_rx.try_recv(), Ok(Message::Toast(_))));
+        drop(context);
+
+        assert_eq!(device_rxs[0].recv(), Some(gi));
+        assert_eq!(device_rxs[0].recv(), None);
+        assert_eq!(device_rxs[1].recv(), Some(hsr));
+        assert_eq!(device_rxs[1].recv(), None);
+        assert_eq!(session.stats().game_packets(), 2);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use super::{CaptureSource, Context, DeviceInfo, DeviceSelection};
+use crate::{
+    app::{Message, State},
+    pcapng::Recording,
+};
+
+pub struct PcapSource {
+    selection: DeviceSelection,
+}
+
+impl PcapSource {
+    pub fn new(selection: DeviceSelection) -> Self {
+        Self { selection }
+    }
+}
+
+impl CaptureSource for PcapSource {
+    fn name(&self) -> String {
+        "pcap".to_string()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        tracing::info!("Running exporter with pcap...");
+        tracing::debug!("Finding devices...");
+
+        let devices = devices(&self.selection)?;
+
+        let packet_filter = port_filter(&context.port_ranges);
+
+        let mut error = anyhow::anyhow!("No capture devices found");
+        let mut started = 0;
+
+        for (i, device) in devices.into_iter().enumerate() {
+            let capture = match open(&device, &packet_filter) {
+                Ok(capture) => capture,
+                Err(e) => {
+                    tracing::info!("Device {i} ({}) can't be opened: {e}", device.name);
+                    error = e;
+                    continue;
+                }
+            };
+
+            let interface_id = context.recording.as_ref().and_then(|recording| {
+                recording
+                    .lock()
+                    .unwrap()
+                    .add_interface(
+                        capture.get_datalink().0 as u16,
+                        &device.name,
+                        device.desc.as_deref(),
+                    )
+                    .inspect_err(|e| tracing::info!("Couldn't add device {i} to pcapng file: {e}"))
+                    .ok()
+            });
+
+            let packet_filter = packet_filter.clone();
+            let recording = interface_id.and(context.recording.clone());
+            let mut context = context.clone();
+            context.device_tx = context
+                .device_tx
+                .for_device(&device.name, capture.get_datalink().0 as u16);
+
+            thread::spawn(move || {
+                capture_device(
+                    i,
+                    &device,
+                    capture,
+                    &packet_filter,
+                    recording.zip(interface_id),
+                    &context,
+                )
+            });
+
+            started += 1;
+        }
+
+        if started == 0 {
+            return Err(error);
+        }
+
+        Ok(())
+    }
+}
+
+fn devices(selection: &DeviceSelection) -> anyhow::Result<Vec<pcap::Device>> {
+    Ok(pcap::Device::list()?
+        .into_iter()
+        .filter(|d| selection.uses(&device_info(d)))
+        .collect())
+}
+
+pub fn device_infos() -> anyhow::Result<Vec<DeviceInfo>> {
+    Ok(pcap::Device::list()?.iter().map(device_info).collect())
+}
+
+fn device_info(device: &pcap::Device) -> DeviceInfo {
+    let connected = device.flags.connection_status == pcap::ConnectionStatus::Connected;
+
+    let mut flags = Vec::new();
+    if device.flags.is_up() {
+        flags.push("up".to_string());
+    }
+    if device.flags.is_running() {
+        flags.push("running".to_string());
+    }
+    if device.flags.is_loopback() {
+        flags.push("loopback".to_string());
+    }
+    if device.flags.is_wireless() {
+        flags.push("wireless".to_string());
+    }
+    flags.push(format!("{:?}", device.flags.connection_status).to_lowercase());
+
+    DeviceInfo {
+        name: device.name.clone(),
+        description: device.desc.clone(),
+        addresses: device
+            .addresses
+            .iter()
+            .map(|a| a.addr.to_string())
+            .collect(),
+        flags,
+        loopback: device.flags.is_loopback(),
+        // Loopback is left to the include_loopback toggle
+        auto: device.flags.is_loopback() || (connected && !device.addresses.is_empty()),
+    }
+}
+
+fn open(device: &pcap::Device, packet_filter: &str) -> anyhow::Result<pcap::Capture<pcap::Active>> {
+    let mut capture = pcap::Capture::from_device(device.clone())?
+        .immediate_mode(true)
+        .promisc(true)
+        .buffer_size(1024 * 1024 * 16) // 16MB
+        // Wake up regularly to notice a stopped session
+        .timeout(250)
+        .open()?;
+
+    capture.filter(packet_filter, true)?;
+
+    Ok(capture)
+}
+
+/// Lets through the udp traffic of the games' ports only
+fn port_filter(port_ranges: &[(u16, u16)]) -> String {
+    port_ranges
+        .iter()
+        .map(|(from, to)| format!("udp portrange {from}-{to}"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+fn capture_device(
+    i: usize,
+    device: &pcap::Device,
+    mut capture: pcap::Capture<pcap::Active>,
+    packet_filter: &str,
+    recording: Option<(Arc<Mutex<Recording>>, u32)>,
+    context: &Context,
+) -> anyhow::Result<()> {
+    let Context {
+        device_tx,
+        message_tx,
+        session,
+        ..
+    } = context;
+
+    while !session.is_stopped() {
+        message_tx
+            .send(Message::Toast({
+                let mut toast = egui_notify::Toast::success(format!("Device {i} Ready~!"));
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+
+        message_tx
+            .send(Message::GoTo(State::Waiting("Running".to_string())))
+            .unwrap();
+        tracing::info!("Device {i} Ready~!");
+
+        let mut has_captured = false;
+        let mut widened = false;
+
+        while !session.is_stopped() {
+            // Other udp traffic is let through while the watchdog diagnoses, the pipeline
+            // drops it
+            if session.is_diagnosing() != widened {
+                widened = !widened;
+                capture.filter(if widened { "udp" } else { packet_filter }, true)?;
+            }
+
+            match capture.next_packet() {
+                Ok(packet) => {
+                    let ts = (packet.header.ts.tv_sec as u64 * 1_000_000_000)
+                        + (packet.header.ts.tv_usec as u64 * 1000);
+                    if !device_tx.send(packet.data, ts)? {
+                        continue;
+                    }
+
+                    if let Some((recording, interface_id)) = &recording {
+                        let _ =
+                            recording
+                                .lock()
+                                .unwrap()
+                                .write_packet(*interface_id, ts, packet.data);
+                    }
+                    has_captured = true;
+                }
+                Err(pcap::Error::TimeoutExpired) => continue,
+                Err(_) if !has_captured => break,
+                Err(e) => return Err(anyhow::anyhow!("{e}")),
+            }
+        }
+
+        if session.is_stopped() {
+            break;
+        }
+
+        message_tx
+            .send(Message::Toast({
+                let mut toast =
+                    egui_notify::Toast::error(format!("Device {i} Error. Starting up again..."));
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+        tracing::info!("Device {i} Error. Starting up again...");
+
+        capture = open(device, packet_filter)?;
+    }
+
+    tracing::info!("Device {i} stopped");
+
+    Ok(())
+}
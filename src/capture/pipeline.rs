@@ -0,0 +1,401 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{Arc, Mutex, mpsc},
+    time::Duration,
+};
+
+use super::{
+    dedup::Dedup,
+    link,
+    stats::{CaptureStats, DeviceStats},
+};
+use crate::app::Message;
+
+/// Packets waiting for the sniffer, around 24 MB of full size frames
+const CAPACITY: usize = 16 * 1024;
+
+/// How far apart in capture time the frames of different devices can reach the queue
+/// and still be put back in order. Capture threads hand their frames over within a
+/// few milliseconds of capturing them.
+const REORDER_WINDOW: Duration = Duration::from_millis(20);
+
+struct Packet {
+    timestamp_ns: u64,
+    device: Option<Arc<DeviceStats>>,
+    data: Vec<u8>,
+}
+
+/// The capture side of the bounded queues in front of the sniffers, one per game.
+///
+/// Sources copy each game frame once, out of the capture library's buffer. The
+/// decoders take ownership of every packet, so there is nothing to hand back.
+#[derive(Clone)]
+pub struct PacketSender {
+    /// Port range and queue of every sniffer
+    routes: Vec<((u16, u16), mpsc::SyncSender<Packet>)>,
+    /// Shared by the senders of all devices
+    dedup: Arc<Mutex<Dedup>>,
+    stats: Arc<CaptureStats>,
+    device: Option<(Arc<DeviceStats>, u16)>,
+    message_tx: mpsc::Sender<Message>,
+}
+
+/// The sniffer side of the queue. Frames of all devices share the queue in the order
+/// their capture threads got to it, the receiver merges them by capture time.
+pub struct PacketReceiver {
+    rx: mpsc::Receiver<Packet>,
+    reorder: RefCell<Reorder>,
+    last_device: RefCell<Option<Arc<DeviceStats>>>,
+}
+
+/// Frames taken off the queue but not passed on yet, oldest capture first
+#[derive(Default)]
+struct Reorder {
+    held: VecDeque<Packet>,
+    /// Newest capture timestamp taken off the queue
+    newest: u64,
+    /// Everything captured up to here can go, the queue went quiet
+    flush_until: u64,
+    disconnected: bool,
+}
+
+impl Reorder {
+    fn push(&mut self, packet: Packet) {
+        self.newest = self.newest.max(packet.timestamp_ns);
+
+        // Mostly in order already, so this rarely looks further back than the last frame
+        let at = self
+            .held
+            .iter()
+            .rposition(|held| held.timestamp_ns <= packet.timestamp_ns)
+            .map_or(0, |i| i + 1);
+        self.held.insert(at, packet);
+    }
+
+    /// The oldest frame, once nothing older can show up anymore
+    fn pop(&mut self) -> Option<Packet> {
+        let oldest = self.held.front()?.timestamp_ns;
+        let window = REORDER_WINDOW.as_nanos() as u64;
+
+        if self.disconnected
+            || self.held.len() >= CAPACITY
+            || oldest.saturating_add(window) <= self.newest
+            || oldest <= self.flush_until
+        {
+            self.held.pop_front()
+        } else {
+            None
+        }
+    }
+}
+
+pub fn channel(
+    port_range: (u16, u16),
+    stats: Arc<CaptureStats>,
+    message_tx: mpsc::Sender<Message>,
+) -> (PacketSender, PacketReceiver) {
+    let (sender, mut receivers) = channels(&[port_range], stats, message_tx);
+    (sender, receivers.remove(0))
+}
+
+/// One queue per port range. Every packet goes to the first range it's in.
+pub fn channels(
+    port_ranges: &[(u16, u16)],
+    stats: Arc<CaptureStats>,
+    message_tx: mpsc::Sender<Message>,
+) -> (PacketSender, Vec<PacketReceiver>) {
+    let (routes, receivers) = port_ranges
+        .iter()
+        .map(|port_range| {
+            let (tx, rx) = mpsc::sync_channel(CAPACITY);
+            let receiver = PacketReceiver {
+                rx,
+                reorder: RefCell::default(),
+                last_device: RefCell::new(None),
+            };
+            ((*port_range, tx), receiver)
+        })
+        .unzip();
+
+    let sender = PacketSender {
+        routes,
+        dedup: Arc::default(),
+        stats,
+        device: None,
+        message_tx,
+    };
+
+    (sender, receivers)
+}
+
+impl PacketSender {
+    /// A sender counting its packets as coming from `name`
+    pub fn for_device(&self, name: &str, link_type: u16) -> PacketSender {
+        if !link::is_supported(link_type) {
+            tracing::info!(
+                "{name} has the unsupported link type {link_type}, ignoring its packets"
+            );
+        }
+
+        PacketSender {
+            device: Some((self.stats.add_device(name), link_type)),
+            ..self.clone()
+        }
+    }
+
+    /// For live captures. Only game traffic is copied and passed on, and the return
+    /// value says whether `data` was. Never blocks: if the sniffer falls behind, the
+    /// packet is dropped and counted, so the capture thread keeps draining the OS buffer
+    /// instead of letting it overflow silently.
+    pub fn send(&self, data: &[u8], timestamp_ns: u64) -> Result<bool, mpsc::SendError<Vec<u8>>> {
+        let Some(tx) = self.route(data) else {
+            return Ok(false);
+        };
+
+        let datagram = self.datagram(data);
+        if !self.claim(datagram) {
+            return Ok(true);
+        }
+
+        let Some(packet) = self.packet(data.to_vec(), timestamp_ns) else {
+            self.unclaim(datagram);
+            return Ok(true);
+        };
+
+        match tx.try_send(packet) {
+            Ok(()) => Ok(true),
+            Err(mpsc::TrySendError::Full(_)) => {
+                self.unclaim(datagram);
+                if self.stats.add_dropped() == 0 {
+                    tracing::info!("Sniffer can't keep up, dropping packets");
+                    let _ = self.message_tx.send(Message::Toast(egui_notify::Toast::warning(
+                        "The exporter can't keep up and dropped packets. If the export fails, try again",
+                    )));
+                }
+                Ok(true)
+            }
+            Err(mpsc::TrySendError::Disconnected(packet)) => Err(mpsc::SendError(packet.data)),
+        }
+    }
+
+    /// For replays, which can wait for the sniffer instead of losing packets
+    pub fn send_blocking(
+        &self,
+        data: Vec<u8>,
+        timestamp_ns: u64,
+    ) -> Result<(), mpsc::SendError<Vec<u8>>> {
+        let Some(tx) = self.route(&data) else {
+            return Ok(());
+        };
+
+        let datagram = self.datagram(&data);
+        if !self.claim(datagram) {
+            return Ok(());
+        }
+
+        let Some(packet) = self.packet(data, timestamp_ns) else {
+            self.unclaim(datagram);
+            return Ok(());
+        };
+
+        tx.send(packet).map_err(|e| mpsc::SendError(e.0.data))
+    }
+
+    /// Counts the frame and picks the queue of the game it belongs to, if any
+    fn route(&self, data: &[u8]) -> Option<&mpsc::SyncSender<Packet>> {
+        let Some((device, link_type)) = &self.device else {
+            return self.routes.first().map(|(_, tx)| tx);
+        };
+
+        let ports = link::udp_ports(*link_type, data);
+        let route = ports.and_then(|(src, dst)| {
+            self.routes
+                .iter()
+                .find(|((from, to), _)| (from..=to).contains(&&src) || (from..=to).contains(&&dst))
+        });
+        device.add_frame(data.len(), ports.is_some(), route.is_some());
+
+        route.map(|(_, tx)| tx)
+    }
+
+    /// The device and the hash of the udp datagram in the frame, if there is one
+    fn datagram(&self, data: &[u8]) -> Option<(&Arc<DeviceStats>, u64)> {
+        let (device, link_type) = self.device.as_ref()?;
+        let udp = link::udp(*link_type, data)?;
+        Some((device, Dedup::hash(udp)))
+    }
+
+    /// Whether the datagram is this device's to pass on, because no other device passed
+    /// on a copy. Checked and remembered at once, so two devices can't both claim it.
+    /// Copies are counted on the device that saw them.
+    fn claim(&self, datagram: Option<(&Arc<DeviceStats>, u64)>) -> bool {
+        let Some((device, hash)) = datagram else {
+            return true;
+        };
+
+        let mut dedup = self.dedup.lock().unwrap();
+        if dedup.is_duplicate(device, hash) {
+            drop(dedup);
+            device.add_duplicate();
+            return false;
+        }
+        dedup.insert(device, hash);
+
+        true
+    }
+
+    /// Gives up a claimed datagram that didn't make it into the queue, so a copy on
+    /// another device can still take its place
+    fn unclaim(&self, datagram: Option<(&Arc<DeviceStats>, u64)>) {
+        if let Some((device, hash)) = datagram {
+            self.dedup.lock().unwrap().remove(device, hash);
+        }
+    }
+
+    /// Turns the frame into Ethernet, if it can be
+    fn packet(&self, data: Vec<u8>, timestamp_ns: u64) -> Option<Packet> {
+        let Some((device, link_type)) = &self.device else {
+            return Some(Packet {
+                timestamp_ns,
+                device: None,
+                data,
+            });
+        };
+
+        Some(Packet {
+            timestamp_ns,
+            device: Some(device.clone()),
+            data: link::normalize(*link_type, data)?,
+        })
+    }
+}
+
+impl PacketReceiver {
+    /// Blocks until the next packet. `None` once every sender is gone.
+    pub fn recv(&self) -> Option<Vec<u8>> {
+        self.recv_timed().map(|(_, data)| data)
+    }
+
+    /// Like `recv`, with the capture timestamp of the packet
+    pub fn recv_timed(&self) -> Option<(u64, Vec<u8>)> {
+        let mut reorder = self.reorder.borrow_mut();
+
+        let packet = loop {
+            if let Some(packet) = reorder.pop() {
+                break packet;
+            }
+            if reorder.disconnected {
+                return None;
+            }
+
+            let next = if reorder.held.is_empty() {
+                self.rx
+                    .recv()
+                    .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+            } else {
+                self.rx.recv_timeout(REORDER_WINDOW)
+            };
+
+            match next {
+                Ok(packet) => reorder.push(packet),
+                // Nothing else is coming for now, whatever is held can go
+                Err(mpsc::RecvTimeoutError::Timeout) => reorder.flush_until = reorder.newest,
+                Err(mpsc::RecvTimeoutError::Disconnected) => reorder.disconnected = true,
+            }
+        };
+
+        *self.last_device.borrow_mut() = packet.device;
+        Some((packet.timestamp_ns, packet.data))
+    }
+
+    /// Counts whether the sniffer got anything out of the last packet
+    pub fn add_decode(&self, decoded: bool) {
+        if let Some(device) = &*self.last_device.borrow() {
+            device.add_decode(decoded);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::link::fixtures;
+
+    /// Ethernet, IPv4 and udp from the game's port 22101
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        fixtures::frame(22101, payload)
+    }
+
+    #[test]
+    fn copies_on_other_devices_are_dropped() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let stats = Arc::new(CaptureStats::default());
+        let (sender, receiver) = channel((22101, 22102), stats.clone(), message_tx);
+        let (wifi, vpn) = (sender.for_device("wifi", 1), sender.for_device("vpn", 1));
+        drop(sender);
+
+        assert!(wifi.send(&frame(b"login"), 1).unwrap());
+        assert!(vpn.send(&frame(b"login"), 1).unwrap());
+        assert!(wifi.send(&frame(b"login"), 2).unwrap());
+        drop((wifi, vpn));
+
+        assert_eq!(receiver.recv(), Some(frame(b"login")));
+        assert_eq!(receiver.recv(), Some(frame(b"login")));
+        assert_eq!(receiver.recv(), None);
+        assert_eq!(stats.duplicates(), 1);
+    }
+
+    #[test]
+    fn copy_of_a_dropped_datagram_is_passed_on() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let stats = Arc::new(CaptureStats::default());
+        let (sender, receiver) = channel((22101, 22102), stats.clone(), message_tx);
+        let (wifi, vpn) = (sender.for_device("wifi", 1), sender.for_device("vpn", 1));
+
+        for i in 0..CAPACITY as u32 {
+            wifi.send(&frame(&i.to_le_bytes()), i as u64).unwrap();
+        }
+        // The queue is full
+        wifi.send(&frame(b"login"), CAPACITY as u64).unwrap();
+        assert_eq!(stats.dropped(), 1);
+
+        receiver.recv();
+        vpn.send(&frame(b"login"), CAPACITY as u64).unwrap();
+        assert_eq!(stats.duplicates(), 0);
+        drop((sender, wifi, vpn));
+
+        let last = std::iter::from_fn(|| receiver.recv()).last();
+        assert_eq!(last, Some(frame(b"login")));
+    }
+
+    #[test]
+    fn frames_of_all_devices_are_merged_by_capture_time() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let stats = Arc::new(CaptureStats::default());
+        let (sender, receiver) = channel((22101, 22102), stats, message_tx);
+        let (wifi, vpn) = (sender.for_device("wifi", 1), sender.for_device("vpn", 1));
+
+        // The vpn's capture thread got to the queue late
+        let ms = 1_000_000;
+        wifi.send(&frame(b"1"), ms).unwrap();
+        wifi.send(&frame(b"3"), 3 * ms).unwrap();
+        vpn.send(&frame(b"0"), 0).unwrap();
+        vpn.send(&frame(b"2"), 2 * ms).unwrap();
+        wifi.send(&frame(b"30"), 30 * ms).unwrap();
+
+        let order: Vec<_> = (0..4).map(|_| receiver.recv_timed().unwrap()).collect();
+        assert_eq!(
+            order,
+            [
+                (0, frame(b"0")),
+                (ms, frame(b"1")),
+                (2 * ms, frame(b"2")),
+                (3 * ms, frame(b"3")),
+            ]
+        );
+
+        // Nothing newer shows up, so the last frame is let go after the window
+        assert_eq!(receiver.recv_timed(), Some((30 * ms, frame(b"30"))));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use super::{CaptureSource, Context, PacketSender, link};
+use crate::{
+    app::{Message, State},
+    pcapng::Recording,
+};
+
+pub struct PktmonSource;
+
+impl CaptureSource for PktmonSource {
+    fn name(&self) -> String {
+        "pktmon".to_string()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        tracing::info!("Running exporter with pktmon...");
+
+        // Fails right away without admin rights
+        let capture = open(&context.port_ranges)?;
+
+        let context = context.clone();
+        thread::spawn(move || capture_pktmon(capture, &context));
+
+        Ok(())
+    }
+}
+
+fn open(port_ranges: &[(u16, u16)]) -> anyhow::Result<pktmon::Capture> {
+    let mut capture = pktmon::Capture::new()?;
+
+    for port in port_ranges.iter().flat_map(|(from, to)| [*from, *to]) {
+        capture
+            .add_filter(pktmon::filter::PktMonFilter {
+                name: "UDP Filter".to_string(),
+                transport_protocol: Some(pktmon::filter::TransportProtocol::UDP),
+                port: port.into(),
+                ..pktmon::filter::PktMonFilter::default()
+            })
+            .unwrap();
+    }
+
+    Ok(capture)
+}
+
+fn capture_pktmon(mut capture: pktmon::Capture, context: &Context) -> anyhow::Result<()> {
+    let Context {
+        port_ranges,
+        message_tx,
+        session,
+        ..
+    } = context;
+
+    // Ethernet from most adapters, bare ip packets from the ones without a link layer
+    let mut devices: Vec<PktmonDevice> = Vec::new();
+
+    while !session.is_stopped() {
+        message_tx
+            .send(Message::Toast({
+                let mut toast = egui_notify::Toast::success("Capture Ready~!".to_string());
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+
+        message_tx
+            .send(Message::GoTo(State::Waiting("Running".to_string())))
+            .unwrap();
+        tracing::info!("Capture Ready~!");
+
+        let mut has_captured = false;
+        capture.start().unwrap();
+
+        while !session.is_stopped() {
+            match capture.next_packet_timeout(std::time::Duration::from_secs(1)) {
+                Ok(packet) => {
+                    let ts = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos() as u64;
+                    let payload = &packet.payload;
+
+                    let link_type = link::ethernet_or_raw(payload);
+                    let index = match devices.iter().position(|(l, ..)| *l == link_type) {
+                        Some(index) => index,
+                        None => {
+                            devices.push(device(context, link_type));
+                            devices.len() - 1
+                        }
+                    };
+                    let (_, device_tx, recording) = &devices[index];
+
+                    if !device_tx.send(payload, ts)? {
+                        continue;
+                    }
+
+                    if let Some((recording, interface_id)) = recording {
+                        let _ = recording
+                            .lock()
+                            .unwrap()
+                            .write_packet(*interface_id, ts, payload);
+                    }
+                    has_captured = true;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    has_captured = true;
+                    continue;
+                }
+                Err(_) if !has_captured => break,
+                Err(e) => return Err(anyhow::anyhow!("{e}")),
+            }
+        }
+
+        if session.is_stopped() {
+            break;
+        }
+
+        message_tx
+            .send(Message::Toast({
+                let mut toast =
+                    egui_notify::Toast::error("Capture Error. Starting up again...".to_string());
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+        tracing::info!("Capture Error. Starting up again...");
+
+        capture = open(port_ranges)?;
+    }
+
+    tracing::info!("Capture stopped");
+
+    Ok(())
+}
+
+type PktmonDevice = (u16, PacketSender, Option<(Arc<Mutex<Recording>>, u32)>);
+
+fn device(context: &Context, link_type: u16) -> PktmonDevice {
+    let name = if link_type == link::LINKTYPE_ETHERNET {
+        "pktmon"
+    } else {
+        "pktmon (ip)"
+    };
+
+    let recording = context.recording.clone().and_then(|recording| {
+        let interface_id = recording
+            .lock()
+            .unwrap()
+            .add_interface(link_type, name, Some("Windows Packet Monitor"))
+            .ok()?;
+        Some((recording, interface_id))
+    });
+
+    (
+        link_type,
+        context.device_tx.for_device(name, link_type),
+        recording,
+    )
+}
+
+pub fn is_elevated() -> bool {
+    unsafe { windows::Win32::UI::Shell::IsUserAnAdmin().into() }
+}
+
+/// Starts a copy of the exporter with admin rights and exits
+pub fn restart_elevated() -> anyhow::Result<()> {
+    use std::{env, os::windows::ffi::OsStrExt};
+    use windows::Win32::System::Console::GetConsoleWindow;
+    use windows::Win32::UI::Shell::{
+        SEE_MASK_NO_CONSOLE, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{GW_OWNER, GetWindow, SW_SHOWNORMAL};
+    use windows::core::PCWSTR;
+    use windows::core::w;
+
+    let args_str = env::args().skip(1).collect::<Vec<_>>().join(" ");
+
+    let exe_path = env::current_exe()
+        .expect("Failed to get current exe")
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    let args = args_str.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+
+    unsafe {
+        let mut options = SHELLEXECUTEINFOW {
+            cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NO_CONSOLE,
+            hwnd: GetWindow(GetConsoleWindow(), GW_OWNER).unwrap_or(GetConsoleWindow()),
+            lpVerb: w!("runas"),
+            lpFile: PCWSTR(exe_path.as_ptr()),
+            lpParameters: PCWSTR(args.as_ptr()),
+            lpDirectory: PCWSTR::null(),
+            nShow: SW_SHOWNORMAL.0,
+            lpIDList: std::ptr::null_mut(),
+            lpClass: PCWSTR::null(),
+            dwHotKey: 0,
+            ..Default::default()
+        };
+
+        ShellExecuteExW(&mut options)?;
+    };
+
+    // Exit the current process since we launched a new elevated one
+    std::process::exit(0);
+}
@@ -0,0 +1,148 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+/// Counters of one capture session, shared by the capture threads, the sniffer and the
+/// waiting screen
+#[derive(Default)]
+pub struct CaptureStats {
+    devices: Mutex<Vec<Arc<DeviceStats>>>,
+    dropped: AtomicU64,
+}
+
+impl CaptureStats {
+    /// A device that shows up again, like a reconnected stream, keeps counting on
+    /// its old row
+    pub fn add_device(&self, name: &str) -> Arc<DeviceStats> {
+        let mut devices = self.devices.lock().unwrap();
+
+        if let Some(device) = devices.iter().find(|d| d.name == name) {
+            return device.clone();
+        }
+
+        let device = Arc::new(DeviceStats {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        devices.push(device.clone());
+        device
+    }
+
+    pub fn devices(&self) -> Vec<Arc<DeviceStats>> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    /// Packets thrown away because the sniffer couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the previous count
+    pub fn add_dropped(&self) -> u64 {
+        self.dropped.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.devices().iter().map(|d| d.frames()).sum()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.devices().iter().map(|d| d.bytes()).sum()
+    }
+
+    pub fn game_packets(&self) -> u64 {
+        self.devices().iter().map(|d| d.game_packets()).sum()
+    }
+
+    pub fn duplicates(&self) -> u64 {
+        self.devices().iter().map(|d| d.duplicates()).sum()
+    }
+
+    pub fn log(&self) {
+        for device in self.devices() {
+            tracing::info!(
+                "{}: {} frames ({} bytes), {} other udp, {} game packets, {} duplicates, {} decoded, {} not decoded",
+                device.name,
+                device.frames(),
+                device.bytes(),
+                device.other_udp(),
+                device.game_packets(),
+                device.duplicates(),
+                device.decoded(),
+                device.failed()
+            );
+        }
+        tracing::info!("{} packets dropped", self.dropped());
+    }
+}
+
+#[derive(Default)]
+pub struct DeviceStats {
+    pub name: String,
+    frames: AtomicU64,
+    bytes: AtomicU64,
+    other_udp: AtomicU64,
+    game_packets: AtomicU64,
+    duplicates: AtomicU64,
+    decoded: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl DeviceStats {
+    pub fn add_frame(&self, len: usize, udp: bool, game_packet: bool) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if game_packet {
+            self.game_packets.fetch_add(1, Ordering::Relaxed);
+        } else if udp {
+            self.other_udp.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn add_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_decode(&self, decoded: bool) {
+        if decoded {
+            self.decoded.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Udp frames outside the game's port range
+    pub fn other_udp(&self) -> u64 {
+        self.other_udp.load(Ordering::Relaxed)
+    }
+
+    /// Frames in the game's port range
+    pub fn game_packets(&self) -> u64 {
+        self.game_packets.load(Ordering::Relaxed)
+    }
+
+    /// Game packets another device already passed on to the sniffer
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Packets the sniffer turned into something
+    pub fn decoded(&self) -> u64 {
+        self.decoded.load(Ordering::Relaxed)
+    }
+
+    /// Packets the sniffer got nothing out of. Includes fragments it's still putting
+    /// together, so this is never zero.
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read},
+    net::TcpListener,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::{CaptureSession, CaptureSource, Context, PacketSender};
+use crate::{
+    app::{Message, State},
+    pcapng::{Packets, PcapngReader, Recording},
+};
+
+/// Reads a live pcap or pcapng stream written by a capture on another machine
+pub struct StreamSource {
+    input: String,
+}
+
+impl StreamSource {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.trim().to_string(),
+        }
+    }
+}
+
+enum Input {
+    Stdin,
+    Pipe(PathBuf),
+    Tcp(TcpListener),
+}
+
+impl CaptureSource for StreamSource {
+    fn name(&self) -> String {
+        "stream".to_string()
+    }
+
+    fn start(self: Box<Self>, context: &Context) -> anyhow::Result<()> {
+        tracing::info!("Running exporter with stream {}...", self.input);
+
+        // Only checks the input here, opening a pipe or accepting a connection blocks
+        // until the other side shows up
+        let input = if self.input.is_empty() || self.input == "-" {
+            Input::Stdin
+        } else if let Some(address) = self.input.strip_prefix("tcp://") {
+            let listener = TcpListener::bind(address)?;
+            // Polled, so a stopped session isn't stuck waiting for a connection
+            listener.set_nonblocking(true)?;
+            Input::Tcp(listener)
+        } else {
+            let path = PathBuf::from(&self.input);
+            if !path.exists() {
+                return Err(anyhow::anyhow!("{} doesn't exist", path.display()));
+            }
+            Input::Pipe(path)
+        };
+
+        let context = context.clone();
+        thread::spawn(move || capture_stream(&self.input, input, &context));
+
+        Ok(())
+    }
+}
+
+/// Sockets are polled, but stdin and pipes block. A stopped session only ends their
+/// thread once the writer sends something or goes away.
+fn capture_stream(name: &str, input: Input, context: &Context) -> anyhow::Result<()> {
+    let Context {
+        message_tx,
+        session,
+        ..
+    } = context;
+
+    while !session.is_stopped() {
+        message_tx
+            .send(Message::GoTo(State::Waiting(format!("Waiting for {name}"))))
+            .unwrap();
+
+        let reader: Box<dyn Read> = match &input {
+            Input::Stdin => Box::new(io::stdin().lock()),
+            Input::Pipe(path) => Box::new(File::open(path)?),
+            Input::Tcp(listener) => match accept(listener, session)? {
+                Some(reader) => Box::new(reader),
+                None => break,
+            },
+        };
+
+        message_tx
+            .send(Message::Toast({
+                let mut toast = egui_notify::Toast::success("Stream Ready~!".to_string());
+                toast.duration(None);
+                toast
+            }))
+            .unwrap();
+
+        message_tx
+            .send(Message::GoTo(State::Waiting("Running".to_string())))
+            .unwrap();
+        tracing::info!("Stream Ready~!");
+
+        match read_stream(BufReader::new(reader), context) {
+            Ok(true) => {}
+            // The sniffer is done
+            Ok(false) => break,
+            Err(e) if session.is_stopped() => tracing::debug!("Stream stopped: {e}"),
+            Err(e) => {
+                message_tx
+                    .send(Message::Toast(egui_notify::Toast::error(format!(
+                        "Stream Error: {e}"
+                    ))))
+                    .unwrap();
+                tracing::info!("Stream Error: {e}");
+            }
+        }
+
+        // Only pipes and sockets get another writer, stdin and plain files are read once
+        match &input {
+            Input::Stdin => break,
+            Input::Pipe(path) if path.is_file() => break,
+            _ => {}
+        }
+    }
+
+    tracing::info!("Stream stopped");
+
+    Ok(())
+}
+
+fn accept(listener: &TcpListener, session: &CaptureSession) -> io::Result<Option<impl Read>> {
+    while !session.is_stopped() {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                tracing::info!("Stream connection from {address}");
+
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+                return Ok(Some(Stoppable::new(stream, session)));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(250));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Feeds one stream to the sniffer until it ends. Returns false once nobody is
+/// listening anymore.
+fn read_stream(reader: impl Read, context: &Context) -> anyhow::Result<bool> {
+    let mut reader = Packets::new(PcapngReader::new(reader)?);
+    let mut devices: Vec<Option<StreamDevice>> = Vec::new();
+
+    while let Some((index, link_type, packet)) = reader.next_packet()? {
+        if context.session.is_stopped() {
+            return Ok(false);
+        }
+
+        if devices.len() <= index {
+            devices.resize_with(index + 1, || None);
+        }
+        let (device_tx, recording) = devices[index].get_or_insert_with(|| {
+            let name = reader.devices()[index].name_or_numbered(index);
+            tracing::info!("Stream interface {name} with link type {link_type}");
+            device(context, &name, link_type)
+        });
+
+        match device_tx.send(&packet.data, packet.timestamp_ns) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(_) => return Ok(false),
+        }
+
+        if let Some((recording, interface_id)) = recording {
+            let _ = recording.lock().unwrap().write_packet(
+                *interface_id,
+                packet.timestamp_ns,
+                &packet.data,
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+type StreamDevice = (PacketSender, Option<(Arc<Mutex<Recording>>, u32)>);
+
+fn device(context: &Context, name: &str, link_type: u16) -> StreamDevice {
+    let name = format!("stream: {name}");
+
+    let recording = context.recording.clone().and_then(|recording| {
+        let interface_id = recording
+            .lock()
+            .unwrap()
+            .add_interface(link_type, &name, Some("Remote capture"))
+            .inspect_err(|e| tracing::info!("Couldn't add {name} to pcapng file: {e}"))
+            .ok()?;
+        Some((recording, interface_id))
+    });
+
+    (context.device_tx.for_device(&name, link_type), recording)
+}
+
+/// Keeps retrying reads that timed out, until the session is stopped
+pub(super) struct Stoppable<R> {
+    inner: R,
+    session: CaptureSession,
+}
+
+impl<R> Stoppable<R> {
+    pub(super) fn new(inner: R, session: &CaptureSession) -> Self {
+        Self {
+            inner,
+            session: session.clone(),
+        }
+    }
+}
+
+impl<R: Read> Read for Stoppable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.inner.read(buf) {
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if self.session.is_stopped() {
+                        return Err(io::Error::other("Capture stopped"));
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::CaptureSession;
+use crate::{
+    app::{Message, State},
+    games::Game,
+};
+
+/// What the capture saw when no game traffic showed up
+pub struct Diagnosis {
+    /// The game captured for, `None` if it was being detected
+    pub game: Option<Game>,
+    pub seconds: u32,
+    /// Name, frames, udp frames on other ports
+    pub devices: Vec<(String, u64, u64)>,
+    pub hints: Vec<String>,
+}
+
+/// Shows the diagnostics if no game packets show up for `seconds` after the capture
+/// started, and again every time the user keeps waiting without any showing up
+pub fn spawn(
+    session: CaptureSession,
+    seconds: u32,
+    port_ranges: Vec<(u16, u16)>,
+    source: String,
+    game: Option<Game>,
+    message_tx: mpsc::Sender<Message>,
+) {
+    thread::spawn(move || {
+        let timeout = Duration::from_secs(seconds as u64);
+        let mut start = Some(Instant::now());
+        session.set_diagnosing(true);
+
+        while !session.is_stopped() {
+            if session.stats().game_packets() > 0 {
+                session.set_diagnosing(false);
+                return;
+            }
+
+            if session.take_rearm() {
+                start = Some(Instant::now());
+            }
+
+            if let Some(since) = start
+                && since.elapsed() >= timeout
+            {
+                let diagnosis = diagnose(&session, seconds, &port_ranges, &source, game);
+                tracing::info!(
+                    "No game traffic after {seconds}s: {}",
+                    diagnosis.hints.join(" ")
+                );
+                message_tx
+                    .send(Message::GoTo(State::Diagnostics(diagnosis)))
+                    .unwrap();
+                start = None;
+            }
+
+            thread::sleep(Duration::from_millis(500));
+        }
+    });
+}
+
+fn diagnose(
+    session: &CaptureSession,
+    seconds: u32,
+    port_ranges: &[(u16, u16)],
+    source: &str,
+    game: Option<Game>,
+) -> Diagnosis {
+    let ports = port_ranges
+        .iter()
+        .map(|(from, to)| format!("{from}-{to}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let devices: Vec<_> = session
+        .stats()
+        .devices()
+        .iter()
+        .map(|d| (d.name.clone(), d.frames(), d.other_udp()))
+        .collect();
+
+    let frames: u64 = devices.iter().map(|d| d.1).sum();
+    let other_udp: u64 = devices.iter().map(|d| d.2).sum();
+
+    let mut hints = Vec::new();
+
+    if frames == 0 {
+        if source == "stream" {
+            hints.push(
+                "Nothing arrived over the stream. Make sure the remote capture is running, writes unbuffered (tcpdump -U) and sends to the address in the settings."
+                    .to_string(),
+            );
+        } else if source == "helper" {
+            hints.push(format!(
+                "The capture helper didn't forward any game traffic on ports {ports}. Its log shows what it captures on."
+            ));
+        } else if source == "pktmon" {
+            hints.push(format!(
+                "pktmon only reports the game's ports ({ports}) and saw nothing on them."
+            ));
+        } else {
+            hints.push(
+                "No udp traffic was captured at all. Pick the adapter you're connected to the internet with under \"Capture devices\", and make sure the exporter runs as admin/root."
+                    .to_string(),
+            );
+        }
+    } else if other_udp > 0 {
+        hints.push(format!(
+            "There is udp traffic, but none on the game's ports {ports}. A VPN or game booster sends the game's traffic through its own encrypted tunnel, so turn it off while exporting. If a game patch moved the ports, they can be changed in games.json."
+        ));
+
+        let quiet: Vec<_> = devices
+            .iter()
+            .filter(|d| d.1 == 0)
+            .map(|d| d.0.as_str())
+            .collect();
+        if !quiet.is_empty() {
+            hints.push(format!("These devices saw nothing: {}", quiet.join(", ")));
+        }
+    }
+
+    hints.push(
+        "The achievements are only sent while logging in, so the exporter has to be running before that. Restart the capture and log in again."
+            .to_string(),
+    );
+
+    if let Some(note) = game.and_then(|game| game.spec().note.as_deref()) {
+        hints.push(note.to_string());
+    }
+
+    Diagnosis {
+        game,
+        seconds,
+        devices,
+        hints,
+    }
+}
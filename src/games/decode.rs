@@ -0,0 +1,327 @@
+//! What the sniffers make of the commands once the game's crate decrypted them. The
+//! crates only know the achievement list, the rest is read with [`proto`].
+
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{Achievement, AchievementSpec, Login, layout::Layout, proto, region};
+
+/// Commands after a handshake that are searched for the uid. The login response comes
+/// first, but the same uid shows up again in later notifies.
+const LOGIN_COMMANDS: usize = 256;
+
+/// Reads the commands of one capture
+pub struct Decoder<'a> {
+    achievement_ids: &'a [u32],
+    spec: &'a AchievementSpec,
+    login: Login,
+    /// Uid-shaped values seen since the handshake and how many commands had them, while
+    /// still searching
+    uids: Option<HashMap<u32, usize>>,
+    commands: usize,
+    /// Where the records of the last achievement list were
+    layout: Option<Layout>,
+    /// Ids in the last achievement list, the only ones updates can be about
+    listed: Vec<u32>,
+    found: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(achievement_ids: &'a [u32], spec: &'a AchievementSpec) -> Self {
+        Self {
+            achievement_ids,
+            spec,
+            login: Login::default(),
+            uids: None,
+            commands: 0,
+            layout: None,
+            listed: Vec::new(),
+            found: false,
+        }
+    }
+
+    /// Every login is a new connection, maybe to another account
+    pub fn handshake(&mut self) {
+        self.login = Login {
+            number: self.login.number + 1,
+            uid: None,
+        };
+        self.uids = Some(HashMap::new());
+        self.commands = 0;
+    }
+
+    /// `list` is the achievement ids and statuses the game's crate read from the
+    /// command, if it's the achievement list. Later commands with records like the
+    /// list's are updates. Returns the achievements stardb knows and the finished ids it
+    /// doesn't, along with the login they came after.
+    pub fn command(
+        &mut self,
+        command_id: u16,
+        data: &[u8],
+        list: Option<Vec<(u32, u32)>>,
+    ) -> Option<(Login, Vec<Achievement>, Vec<u32>)> {
+        match &self.spec.login {
+            Some(spec) => {
+                if command_id == spec.command_id
+                    && let Some(uid) = proto::varint_field(data, spec.uid_field)
+                {
+                    self.logged_in(u32::try_from(uid).ok()?);
+                    return None;
+                }
+            }
+            None => self.search_uid(data),
+        }
+
+        let Some(list) = list else {
+            return self.update(command_id, data);
+        };
+        tracing::info!("Found achievement packet");
+        // A uid found after this would split the account
+        self.uids = None;
+
+        // Progress and finish times, if the records can be found in the command
+        self.layout = Layout::learn(data, &list);
+        let records = match &self.layout {
+            Some(layout) => layout.read(data).unwrap_or_default(),
+            None => {
+                tracing::info!("Couldn't find the achievement records, progress stays unknown");
+                Vec::new()
+            }
+        };
+        self.listed = list.iter().map(|&(id, _)| id).collect();
+
+        let list = list
+            .into_iter()
+            .map(|(id, status)| {
+                let record = records.iter().find(|record| record.id == id);
+                Achievement {
+                    id,
+                    status,
+                    current: record.and_then(|record| record.current),
+                    total: record.and_then(|record| record.total),
+                    finished_at: record.and_then(|record| record.finished_at),
+                }
+            })
+            .collect();
+        let (achievements, unknown) = self.split(list);
+
+        if achievements.is_empty() {
+            return None;
+        }
+        self.found = true;
+
+        Some((self.login, achievements, unknown))
+    }
+
+    /// Achievements that made progress or were finished since the list
+    fn update(
+        &mut self,
+        command_id: u16,
+        data: &[u8],
+    ) -> Option<(Login, Vec<Achievement>, Vec<u32>)> {
+        if let Some(spec) = &self.spec.updates
+            && command_id != spec.command_id
+        {
+            return None;
+        }
+
+        let records = self.layout.as_ref()?.find(data)?;
+        if !records
+            .iter()
+            .all(|record| self.listed.contains(&record.id))
+        {
+            return None;
+        }
+        tracing::info!("Found achievement update");
+
+        let (achievements, unknown) = self.split(records);
+        Some((self.login, achievements, unknown))
+    }
+
+    /// Separates the finished achievements stardb doesn't know
+    fn split(&self, records: Vec<Achievement>) -> (Vec<Achievement>, Vec<u32>) {
+        let mut achievements = Vec::new();
+        let mut unknown = Vec::new();
+
+        for achievement in records {
+            if self.achievement_ids.contains(&achievement.id) {
+                achievements.push(achievement);
+            } else if achievement.is_finished() {
+                unknown.push(achievement.id);
+            }
+        }
+
+        if !unknown.is_empty() {
+            tracing::info!("{} finished achievements aren't on stardb", unknown.len());
+        }
+
+        (achievements, unknown)
+    }
+
+    pub fn finish(self) -> anyhow::Result<()> {
+        if !self.found {
+            return Err(anyhow::anyhow!("No achievements found"));
+        }
+
+        Ok(())
+    }
+
+    fn logged_in(&mut self, uid: u32) {
+        tracing::info!("Logged in as {uid}");
+        self.login.uid = Some(uid);
+        self.uids = None;
+    }
+
+    /// Without a [`super::LoginSpec`], the uid is the first uid-shaped value at the top
+    /// level of two commands after the handshake. Anything else that size, like ip
+    /// addresses, rarely repeats.
+    fn search_uid(&mut self, data: &[u8]) {
+        let Some(uids) = &mut self.uids else {
+            return;
+        };
+
+        self.commands += 1;
+        if self.commands > LOGIN_COMMANDS {
+            tracing::info!("No uid after the login");
+            self.uids = None;
+            return;
+        }
+
+        let Some(values) = proto::fields(data) else {
+            return;
+        };
+
+        let mut seen = Vec::new();
+        let mut found = None;
+
+        for (_, value) in values {
+            let proto::Value::Varint(value) = value else {
+                continue;
+            };
+            let Some(uid) = u32::try_from(value).ok().filter(|&uid| is_uid(uid)) else {
+                continue;
+            };
+            if seen.contains(&uid) {
+                continue;
+            }
+            seen.push(uid);
+
+            let count = uids.entry(uid).or_default();
+            *count += 1;
+            if *count >= 2 && found.is_none() {
+                found = Some(uid);
+            }
+        }
+
+        if let Some(uid) = found {
+            self.logged_in(uid);
+        }
+    }
+}
+
+/// Nine digits with a known region in front, or ten for the newer Asia uids. Server
+/// times in seconds are that long too.
+fn is_uid(uid: u32) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+
+    region(uid) != "Unknown region" && (uid as u64).abs_diff(now) > 24 * 60 * 60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::{Game, proto::fixtures::varint};
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn the_uid_is_read_from_the_commands_after_the_login() {
+        for game in [Game::Hsr, Game::Gi] {
+            let spec = game.achievement_spec().unwrap();
+            let mut decoder = Decoder::new(&[1, 2], spec);
+            decoder.handshake();
+
+            // Token request with an ip address, and the response with the uid and the
+            // server time
+            let request = [varint(3, 800_000_040), varint(7, 0x0a00_0001)].concat();
+            let response = [
+                varint(2, 3),
+                varint(5, now()),
+                varint(11, 800_000_040),
+                varint(12, 100_000_001),
+            ]
+            .concat();
+            assert!(decoder.command(10, &request, None).is_none());
+            assert_eq!(decoder.login.uid, None);
+            assert!(decoder.command(11, &response, None).is_none());
+
+            let (login, achievements, unknown) = decoder
+                .command(12, &[], Some(vec![(1, 2), (3, 3), (4, 1)]))
+                .unwrap();
+            assert_eq!((login.number, login.uid), (1, Some(800_000_040)));
+            assert_eq!(achievements.len(), 1);
+            assert_eq!(unknown, vec![3]);
+            assert!(decoder.finish().is_ok());
+        }
+    }
+
+    #[test]
+    fn server_times_and_single_values_arent_uids() {
+        let spec = Game::Gi.achievement_spec().unwrap();
+        let mut decoder = Decoder::new(&[1], spec);
+        decoder.handshake();
+
+        decoder.command(
+            10,
+            &[varint(1, now()), varint(2, 300_000_000)].concat(),
+            None,
+        );
+        decoder.command(
+            11,
+            &[varint(1, now()), varint(4, 1_800_000_001)].concat(),
+            None,
+        );
+
+        let (login, _, _) = decoder.command(13, &[], Some(vec![(1, 1)])).unwrap();
+        assert_eq!(login.uid, None);
+
+        // Not after the achievement list either
+        decoder.command(14, &varint(4, 1_800_000_001), None);
+        let (login, _, _) = decoder.command(15, &[], Some(vec![(1, 1)])).unwrap();
+        assert_eq!(login.uid, None);
+    }
+
+    #[test]
+    fn a_login_spec_replaces_the_search() {
+        let mut spec = serde_json::to_value(Game::Hsr.achievement_spec().unwrap()).unwrap();
+        spec["login"] = serde_json::json!({"command_id": 11, "uid_field": 4});
+        let spec: AchievementSpec = serde_json::from_value(spec).unwrap();
+        let mut decoder = Decoder::new(&[1], &spec);
+        decoder.handshake();
+
+        decoder.command(10, &varint(1, 700_000_001), None);
+        decoder.command(12, &varint(1, 700_000_001), None);
+        decoder.command(11, &varint(4, 600_000_002), None);
+
+        let (login, _, _) = decoder.command(13, &[], Some(vec![(1, 1)])).unwrap();
+        assert_eq!(login.uid, Some(600_000_002));
+    }
+
+    #[test]
+    fn nothing_found_without_known_achievements() {
+        let spec = Game::Hsr.achievement_spec().unwrap();
+        let mut decoder = Decoder::new(&[1], spec);
+
+        assert!(decoder.command(1, &[], Some(vec![(2, 2)])).is_none());
+        assert!(decoder.finish().is_err());
+    }
+}
@@ -2,61 +2,68 @@ use std::{
     collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
-    path::PathBuf,
-    sync::mpsc,
+    path::{Path, PathBuf},
 };
 
-use auto_artifactarium::{GamePacket, GameSniffer, matches_achievement_packet};
+use auto_artifactarium::{ConnectionPacket, GamePacket, GameSniffer, matches_achievement_packet};
 use base64::prelude::*;
 
+use super::{Achievement, AchievementSpec, Login, decode::Decoder};
+use crate::capture::PacketReceiver;
+
 use regex::Regex;
 
+/// Hands every achievement packet to `on_achievements` until it returns false, along
+/// with the login it came after
 pub fn sniff(
     achievement_ids: &[u32],
-    device_rx: &mpsc::Receiver<Vec<u8>>,
-) -> anyhow::Result<Vec<u32>> {
-    let keys = load_keys()?;
+    spec: &AchievementSpec,
+    device_rx: &PacketReceiver,
+    mut on_achievements: impl FnMut(Login, Vec<Achievement>, Vec<u32>) -> bool,
+) -> anyhow::Result<()> {
+    let keys = load_keys(spec.keys.as_deref())?;
+
     let mut sniffer = GameSniffer::new().set_initial_keys(keys);
+    let mut decoder = Decoder::new(achievement_ids, spec);
 
-    let mut achievements = Vec::new();
+    while let Some(data) = device_rx.recv() {
+        let packet = sniffer.receive_packet(data);
+        device_rx.add_decode(packet.is_some());
 
-    while let Ok(data) = device_rx.recv() {
-        let Some(GamePacket::Commands(commands)) = sniffer.receive_packet(data) else {
-            continue;
+        let commands = match packet {
+            Some(GamePacket::Commands(commands)) => commands,
+            Some(GamePacket::Connection(ConnectionPacket::HandshakeEstablished)) => {
+                decoder.handshake();
+                continue;
+            }
+            _ => continue,
         };
 
         for command in commands {
-            if let Some(read_achievements) = matches_achievement_packet(&command) {
-                tracing::info!("Found achievement packet");
-
-                if !achievements.is_empty() {
-                    continue;
-                }
-
-                for achievement in read_achievements {
-                    if achievement_ids.contains(&achievement.id)
-                        && (achievement.status == 2 || achievement.status == 3)
-                    {
-                        achievements.push(achievement.id);
-                    }
-                }
+            let list = matches_achievement_packet(&command).map(|achievements| {
+                achievements
+                    .into_iter()
+                    .map(|achievement| (achievement.id, achievement.status))
+                    .collect()
+            });
+
+            if let Some((login, achievements, unknown)) =
+                decoder.command(command.command_id, &command.proto_data, list)
+                && !on_achievements(login, achievements, unknown)
+            {
+                return Ok(());
             }
         }
-
-        if !achievements.is_empty() {
-            break;
-        }
-    }
-
-    if achievements.is_empty() {
-        return Err(anyhow::anyhow!("No achievements found"));
     }
 
-    Ok(achievements)
+    decoder.finish()
 }
 
-fn load_keys() -> anyhow::Result<HashMap<u16, Vec<u8>>> {
-    let keys: HashMap<u16, String> = serde_json::from_slice(include_bytes!("../../keys/gi.json"))?;
+fn load_keys(path: Option<&Path>) -> anyhow::Result<HashMap<u16, Vec<u8>>> {
+    let keys: HashMap<u16, String> = match path {
+        Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+        None => serde_json::from_slice(include_bytes!("../../keys/gi.json"))?,
+    };
 
     let mut keys_bytes = HashMap::new();
 
@@ -0,0 +1,292 @@
+//! Where the achievement records sit in a command. The field numbers are shuffled with
+//! every patch, so they're learned from the achievement list, matched against the ids
+//! and statuses the game's crate read from it.
+
+use std::collections::HashMap;
+
+use super::{Achievement, proto};
+
+/// Finish times are unix seconds after the games came out
+const FINISHED_AFTER: u64 = 1_577_836_800;
+
+/// The varint fields of one record
+type Record = HashMap<u32, u64>;
+
+pub struct Layout {
+    /// Top level field of the records
+    records: u32,
+    id: u32,
+    status: u32,
+    current: Option<u32>,
+    total: Option<u32>,
+    finished_at: Option<u32>,
+    /// Every field the records had
+    fields: Vec<u32>,
+}
+
+impl Layout {
+    /// Finds the records in the list command `data` that the crate read `list` from.
+    /// Progress and finish time are the fields left over that look like them.
+    pub fn learn(data: &[u8], list: &[(u32, u32)]) -> Option<Self> {
+        let fields = proto::fields(data)?;
+
+        for number in embedded(&fields) {
+            let Some(records) = records(&fields, number) else {
+                continue;
+            };
+            if records.len() < list.len() {
+                continue;
+            }
+
+            let mut record_fields: Vec<_> =
+                records.iter().flat_map(|r| r.keys()).copied().collect();
+            record_fields.sort_unstable();
+            record_fields.dedup();
+
+            for &id in &record_fields {
+                let by_id: HashMap<_, _> = records.iter().map(|r| (get(r, id), r)).collect();
+
+                for &status in &record_fields {
+                    let matches = id != status
+                        && list.iter().all(|&(i, s)| {
+                            by_id
+                                .get(&(i as u64))
+                                .is_some_and(|r| get(r, status) == s as u64)
+                        });
+                    if !matches {
+                        continue;
+                    }
+
+                    let mut rest: Vec<_> = record_fields
+                        .iter()
+                        .copied()
+                        .filter(|&field| field != id && field != status)
+                        .collect();
+
+                    let times: Vec<_> = rest
+                        .iter()
+                        .copied()
+                        .filter(|&field| {
+                            let values = records.iter().map(|r| get(r, field));
+                            values.clone().any(|value| value != 0)
+                                && values.clone().all(|value| {
+                                    value == 0
+                                        || (FINISHED_AFTER..=u32::MAX as u64).contains(&value)
+                                })
+                        })
+                        .collect();
+                    let finished_at = match times[..] {
+                        [field] => Some(field),
+                        _ => None,
+                    };
+                    rest.retain(|&field| Some(field) != finished_at);
+
+                    // Progress never goes past its goal
+                    let below = |a, b| records.iter().all(|r| get(r, a) <= get(r, b));
+                    let (current, total) = match rest[..] {
+                        [a, b] if below(a, b) => (Some(a), Some(b)),
+                        [a, b] if below(b, a) => (Some(b), Some(a)),
+                        _ => (None, None),
+                    };
+
+                    return Some(Self {
+                        records: number,
+                        id,
+                        status,
+                        current,
+                        total,
+                        finished_at,
+                        fields: record_fields,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The records of the list command this was learned from
+    pub fn read(&self, data: &[u8]) -> Option<Vec<Achievement>> {
+        self.achievements(&records(&proto::fields(data)?, self.records)?)
+    }
+
+    /// Records with this layout in any field of `data`, like the notifies send them
+    /// when achievements make progress
+    pub fn find(&self, data: &[u8]) -> Option<Vec<Achievement>> {
+        let fields = proto::fields(data)?;
+
+        embedded(&fields).into_iter().find_map(|number| {
+            let records = records(&fields, number)?;
+            let fits = records.iter().all(|record| {
+                record.contains_key(&self.id)
+                    && (1..=3).contains(&get(record, self.status))
+                    && record.keys().all(|field| self.fields.contains(field))
+            });
+
+            fits.then(|| self.achievements(&records))?
+        })
+    }
+
+    fn achievements(&self, records: &[Record]) -> Option<Vec<Achievement>> {
+        records
+            .iter()
+            .map(|record| {
+                let field = |field: Option<u32>| {
+                    field
+                        .and_then(|field| u32::try_from(get(record, field)).ok())
+                        .filter(|&value| value != 0)
+                };
+
+                Some(Achievement {
+                    id: u32::try_from(*record.get(&self.id)?).ok()?,
+                    status: u32::try_from(get(record, self.status)).ok()?,
+                    current: self
+                        .current
+                        .and_then(|current| u32::try_from(get(record, current)).ok()),
+                    total: field(self.total),
+                    finished_at: field(self.finished_at),
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|achievements| !achievements.is_empty())
+    }
+}
+
+/// Numbers of the fields that might be embedded messages
+fn embedded(fields: &[(u32, proto::Value)]) -> Vec<u32> {
+    let mut numbers: Vec<_> = fields
+        .iter()
+        .filter(|(_, value)| matches!(value, proto::Value::Bytes(_)))
+        .map(|&(number, _)| number)
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+/// Every `number` field in `fields` as a record, if they're all messages of varints
+fn records(fields: &[(u32, proto::Value)], number: u32) -> Option<Vec<Record>> {
+    fields
+        .iter()
+        .filter(|&&(field, _)| field == number)
+        .map(|(_, value)| {
+            let proto::Value::Bytes(data) = value else {
+                return None;
+            };
+
+            proto::fields(data)?
+                .into_iter()
+                .map(|(field, value)| match value {
+                    proto::Value::Varint(value) => Some((field, value)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Fields that are zero aren't sent
+fn get(record: &Record, field: u32) -> u64 {
+    record.get(&field).copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::proto::fixtures::{bytes, message, varint};
+
+    fn record(fields: &[(u32, u64)]) -> Vec<u8> {
+        message(6, fields)
+    }
+
+    #[test]
+    fn progress_and_finish_time_are_read_from_the_records() {
+        // id 3, status 9, current 2, total 14, finished at 11
+        let data = [
+            varint(1, 42),
+            bytes(4, b"abcdef"),
+            record(&[(3, 80001), (9, 3), (2, 5), (14, 5), (11, 1_700_000_000)]),
+            record(&[(3, 80002), (9, 1), (2, 0), (14, 10), (11, 0)]),
+            record(&[(3, 80003), (9, 1), (2, 7), (14, 10), (11, 0)]),
+            bytes(4, b""),
+        ]
+        .concat();
+        let list = [(80001, 3), (80002, 1), (80003, 1)];
+
+        let layout = Layout::learn(&data, &list).unwrap();
+        let read: Vec<_> = layout
+            .read(&data)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.id, a.status, a.current, a.total, a.finished_at))
+            .collect();
+
+        assert_eq!(
+            read,
+            [
+                (80001, 3, Some(5), Some(5), Some(1_700_000_000)),
+                (80002, 1, Some(0), Some(10), None),
+                (80003, 1, Some(7), Some(10), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn fields_that_cant_be_told_stay_unknown() {
+        // Only one more field, that isn't a time
+        let data = [
+            record(&[(1, 80001), (2, 2), (4, 3)]),
+            record(&[(1, 80002), (2, 1), (4, 1)]),
+        ]
+        .concat();
+
+        let layout = Layout::learn(&data, &[(80001, 2), (80002, 1)]).unwrap();
+        let read = layout.read(&data).unwrap();
+
+        assert_eq!((read[0].id, read[0].status), (80001, 2));
+        assert_eq!(
+            (read[0].current, read[0].total, read[0].finished_at),
+            (None, None, None)
+        );
+        assert!(Layout::learn(&data, &[(80001, 1)]).is_none());
+    }
+
+    #[test]
+    fn updates_are_found_in_any_field() {
+        let list = [
+            record(&[(3, 80001), (9, 1), (2, 1), (14, 5)]),
+            record(&[(3, 80002), (9, 2), (2, 1), (14, 1), (11, 1_700_000_000)]),
+        ]
+        .concat();
+        let layout = Layout::learn(&list, &[(80001, 1), (80002, 2)]).unwrap();
+
+        let update = [
+            varint(1, 7),
+            message(
+                12,
+                &[(3, 80001), (9, 2), (2, 5), (14, 5), (11, 1_750_000_000)],
+            ),
+        ]
+        .concat();
+        let found = layout.find(&update).unwrap();
+        assert_eq!(
+            (
+                found[0].id,
+                found[0].status,
+                found[0].current,
+                found[0].total
+            ),
+            (80001, 2, Some(5), Some(5))
+        );
+        assert_eq!(found[0].finished_at, Some(1_750_000_000));
+
+        // Records of something else
+        assert!(
+            layout
+                .find(&message(12, &[(3, 80001), (9, 2), (4, 1)]))
+                .is_none()
+        );
+        assert!(layout.find(&message(12, &[(3, 80001), (9, 7)])).is_none());
+        assert!(layout.find(&message(12, &[(9, 2)])).is_none());
+    }
+}
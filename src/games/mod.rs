@@ -1,22 +1,97 @@
+mod decode;
 mod gi;
 mod hsr;
+mod layout;
+mod proto;
+mod spec;
+mod unknown;
 mod zzz;
 
 use std::{
     path::{Path, PathBuf},
-    sync::mpsc,
+    sync::{Arc, Mutex, mpsc},
     thread,
 };
 
-#[cfg(not(any(feature = "pktmon", feature = "pcap")))]
-compile_error!("at least one of the features \"pktmon\" or \"pcap\" must be enabled");
-#[cfg(all(feature = "pktmon", feature = "pcap"))]
-compile_error!("at most one of the features \"pktmon\" or \"pcap\" must be enabled");
-
-use crate::app::{Message, State};
-use crate::pcapng::{PcapngWriter, get_pcapng_path};
+use crate::app::{Message, Settings, State};
+use crate::capture::{self, CaptureSession, CaptureSource, FileSource, PacketReceiver};
+use crate::pcapng::{self, Recording};
 use regex::Regex;
 
+pub use spec::{AchievementSpec, LoginSpec, builtin_port_ranges};
+
+/// One achievement as the game sent it
+#[derive(Clone, PartialEq, serde::Serialize)]
+pub struct Achievement {
+    pub id: u32,
+    /// 1 is unfinished, 2 finished and 3 finished with the reward taken
+    pub status: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u32>,
+    /// Unix timestamp in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u32>,
+}
+
+impl Achievement {
+    pub fn is_finished(&self) -> bool {
+        self.status == 2 || self.status == 3
+    }
+}
+
+/// The login an achievement packet came after
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Login {
+    /// Handshakes seen before it
+    pub number: usize,
+    /// Found in the commands after the login, or where the game's [`LoginSpec`] says
+    pub uid: Option<u32>,
+}
+
+/// The achievements of one account. Logging into an alt during a capture starts a new
+/// one, so they don't get mixed up. Without a uid, every login counts as another account.
+#[derive(Clone, PartialEq)]
+pub struct Account {
+    login: Login,
+    pub name: String,
+    pub achievements: Vec<Achievement>,
+    /// Finished ids stardb doesn't know
+    pub unknown: Vec<u32>,
+}
+
+impl Account {
+    fn is_login(&self, login: Login) -> bool {
+        match login.uid {
+            // Reconnecting to the same account continues it
+            Some(uid) => self.login.uid == Some(uid),
+            None => self.login == login,
+        }
+    }
+
+    pub fn finished(&self) -> Vec<u32> {
+        self.achievements
+            .iter()
+            .filter(|a| a.is_finished())
+            .map(|a| a.id)
+            .collect()
+    }
+}
+
+/// The server of a uid, from the digits before the last eight
+fn region(uid: u32) -> &'static str {
+    match uid / 100_000_000 {
+        1..=4 => "China",
+        5 => "China, Bilibili",
+        6 => "America",
+        7 => "Europe",
+        8 | 18 => "Asia",
+        9 => "TW, HK, MO",
+        _ => "Unknown region",
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum Game {
     Hsr,
@@ -24,96 +99,257 @@ pub enum Game {
     Zzz,
 }
 
+impl std::str::FromStr for Game {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Game::ALL
+            .into_iter()
+            .find(|game| game.id() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown game \"{s}\""))
+    }
+}
+
 impl Game {
-    pub fn achievements(self, message_tx: &mpsc::Sender<Message>) {
+    pub const ALL: [Game; 3] = [Game::Hsr, Game::Gi, Game::Zzz];
+
+    pub fn achievements(
+        self,
+        settings: &Settings,
+        message_tx: &mpsc::Sender<Message>,
+    ) -> CaptureSession {
+        capture(
+            vec![self],
+            capture::live_sources(settings, settings.devices.get(Some(self))),
+            Some(settings.clone()),
+            message_tx,
+        )
+    }
+
+    pub fn replay(self, path: PathBuf, message_tx: &mpsc::Sender<Message>) -> CaptureSession {
+        capture(
+            vec![self],
+            vec![Box::new(FileSource::new(path))],
+            None,
+            message_tx,
+        )
+    }
+
+    /// Writes a copy of `input` without the user's addresses and without non-game traffic
+    pub fn sanitize_capture(
+        self,
+        input: PathBuf,
+        output: PathBuf,
+        message_tx: &mpsc::Sender<Message>,
+    ) {
         let message_tx = message_tx.clone();
 
         thread::spawn(move || {
-            let achievement_ids = match self.achievement_ids() {
-                Ok(achievement_ids) => achievement_ids,
+            let result = self.achievement_spec().and_then(|spec| {
+                let (from, to) = spec.port_range;
+                let comment = format!("{} capture, udp ports {from}-{to}, sanitized", self.name());
+
+                Ok(pcapng::anonymize(
+                    &input,
+                    &output,
+                    &comment,
+                    spec.port_range,
+                )?)
+            });
+
+            match result {
+                Ok(stats) => {
+                    tracing::info!(
+                        "Sanitized {} into {}: kept {} packets, dropped {}",
+                        input.display(),
+                        output.display(),
+                        stats.kept,
+                        stats.dropped
+                    );
+                    message_tx
+                        .send(Message::Toast(egui_notify::Toast::success(format!(
+                            "Saved {} game packets ({} others removed)",
+                            stats.kept, stats.dropped
+                        ))))
+                        .unwrap();
+                }
                 Err(e) => {
                     message_tx
-                        .send(Message::GoTo(State::Error(e.to_string())))
+                        .send(Message::Toast(egui_notify::Toast::error(format!(
+                            "Error sanitizing capture: {e}"
+                        ))))
                         .unwrap();
-                    return;
                 }
-            };
+            }
+        });
+    }
 
-            let (device_tx, device_rx) = mpsc::channel();
-            #[cfg(feature = "pcap")]
-            {
-                let devices = match self.devices() {
-                    Ok(devices) => devices,
-                    Err(e) => {
-                        message_tx
-                            .send(Message::GoTo(State::Error(e.to_string())))
-                            .unwrap();
-                        return;
-                    }
-                };
+    /// Shows what `sniff_packets` finds, normally [`Game::sniff_packets`] on the
+    /// game's queue. With `detect`, the UI switches over to this game once it has a
+    /// result. With `keep_watching`, later updates change the results until the session
+    /// is stopped. Returns whether the achievements were exported.
+    fn sniff(
+        self,
+        achievement_ids: &[u32],
+        sniff_packets: impl FnOnce(
+            &mut dyn FnMut(Login, Vec<Achievement>, Vec<u32>) -> bool,
+        ) -> anyhow::Result<()>,
+        detect: bool,
+        keep_watching: bool,
+        session: &CaptureSession,
+        message_tx: &mpsc::Sender<Message>,
+    ) -> bool {
+        let mut accounts: Vec<Account> = Vec::new();
+        let mut exported = false;
+        let unknown_path = unknown::path()
+            .inspect_err(|e| tracing::info!("Can't remember the unknown achievements: {e}"))
+            .ok();
+
+        let result = sniff_packets(&mut |login, update, unknown_ids| {
+            // Cancelled by the user, who already left the waiting screen, or another
+            // game's sniffer was first
+            if !exported && !session.claim() {
+                return false;
+            }
 
-                for (i, device) in devices.into_iter().enumerate() {
-                    let device_tx = device_tx.clone();
-                    let message_tx = message_tx.clone();
-                    std::thread::spawn(move || {
-                        self.capture_device_pcap(i, device, &device_tx, &message_tx)
+            let index = match accounts.iter().position(|a| a.is_login(login)) {
+                Some(index) => index,
+                None => {
+                    if !accounts.is_empty() {
+                        tracing::info!("New login, keeping its achievements apart");
+                    }
+                    accounts.push(Account {
+                        login,
+                        name: match login.uid {
+                            Some(uid) => format!("{uid} ({})", region(uid)),
+                            None => format!("Login {}", accounts.len() + 1),
+                        },
+                        achievements: Vec::new(),
+                        unknown: Vec::new(),
                     });
+                    accounts.len() - 1
+                }
+            };
+            let previous = accounts[index].clone();
+            let account = &mut accounts[index];
+
+            for achievement in update {
+                match account
+                    .achievements
+                    .iter_mut()
+                    .find(|a| a.id == achievement.id)
+                {
+                    Some(known) => {
+                        if achievement.is_finished() && !known.is_finished() {
+                            tracing::info!("Achievement {} finished", achievement.id);
+                        }
+                        *known = achievement;
+                    }
+                    None => account.achievements.push(achievement),
                 }
             }
-            #[cfg(feature = "pktmon")]
-            {
-                let device_tx = device_tx.clone();
-                let message_tx = message_tx.clone();
-                std::thread::spawn(move || self.capture_device_pktmon(&device_tx, &message_tx));
+
+            // Not being able to remember them shouldn't cost the export
+            if let Err(e) = unknown::reconcile(
+                self,
+                achievement_ids,
+                unknown_path.as_deref(),
+                account,
+                &unknown_ids,
+            ) {
+                tracing::info!("Couldn't update the unknown achievements: {e}");
             }
 
-            let achievements = match self {
-                Game::Hsr => hsr::sniff(&achievement_ids, &device_rx),
-                Game::Gi => gi::sniff(&achievement_ids, &device_rx),
-                _ => unimplemented!(),
-            };
-            let achievements = match achievements {
-                Ok(achievements) => achievements,
-                Err(e) => {
-                    message_tx
-                        .send(Message::GoTo(State::Error(e.to_string())))
-                        .unwrap();
-                    return;
+            if !exported {
+                exported = true;
+
+                if detect {
+                    tracing::info!("Detected {}", self.name());
+                    message_tx.send(Message::Game(self)).unwrap();
                 }
-            };
 
+                message_tx
+                    .send(Message::GoTo(State::Achievements(accounts.clone(), 0)))
+                    .unwrap();
+            } else if accounts[index] != previous {
+                message_tx
+                    .send(Message::Achievements(self, accounts.clone(), index))
+                    .unwrap();
+            }
+
+            keep_watching
+        });
+
+        if exported {
+            session.stop();
+            return true;
+        }
+
+        // Stopping here would end the capture of the sniffer that was first
+        if !session.claim() {
+            return false;
+        }
+        session.stop();
+
+        if let Err(e) = result {
             message_tx
-                .send(Message::GoTo(State::Achievements(achievements)))
+                .send(Message::GoTo(State::Error(e.to_string())))
                 .unwrap();
-        });
+        }
+
+        false
     }
 
-    pub fn game_path(self) -> anyhow::Result<PathBuf> {
+    /// Runs the game's sniffer until the queue closes, calling `on_achievements` with
+    /// every achievement list until it returns false
+    pub fn sniff_packets(
+        self,
+        achievement_ids: &[u32],
+        device_rx: &PacketReceiver,
+        on_achievements: impl FnMut(Login, Vec<Achievement>, Vec<u32>) -> bool,
+    ) -> anyhow::Result<()> {
+        let spec = self.achievement_spec()?;
+
         match self {
-            Game::Hsr => hsr::game_path(),
-            Game::Gi => gi::game_path(),
-            Game::Zzz => zzz::game_path(),
+            Game::Hsr => hsr::sniff(achievement_ids, spec, device_rx, on_achievements),
+            Game::Gi => gi::sniff(achievement_ids, spec, device_rx, on_achievements),
+            Game::Zzz => Err(self.unsupported()),
         }
     }
 
-    pub fn achievement_url(self) -> String {
-        let prefix = match self {
-            Game::Hsr => "",
-            Game::Gi => "genshin/",
-            Game::Zzz => "zzz/",
-        };
+    pub fn name(self) -> &'static str {
+        &self.spec().name
+    }
 
-        format!("https://stardb.gg/{prefix}achievement-tracker")
+    fn id(self) -> &'static str {
+        &self.spec().id
     }
 
-    pub fn pull_url(self) -> String {
-        let path = match self {
-            Game::Hsr => "warp-tracker",
-            Game::Gi => "genshin/wish-tracker",
-            Game::Zzz => "zzz/signal-tracker",
-        };
+    /// Whether the achievement exporter is configured and there's a sniffer for the game
+    pub fn supports_achievements(self) -> bool {
+        self.spec().achievements.is_some() && matches!(self, Game::Hsr | Game::Gi)
+    }
+
+    pub fn achievement_spec(self) -> anyhow::Result<&'static AchievementSpec> {
+        match &self.spec().achievements {
+            Some(spec) if self.supports_achievements() => Ok(spec),
+            _ => Err(self.unsupported()),
+        }
+    }
 
-        format!("https://stardb.gg/{path}")
+    fn unsupported(self) -> anyhow::Error {
+        anyhow::anyhow!(
+            "The achievement exporter doesn't support {} yet",
+            self.name()
+        )
+    }
+
+    pub fn game_path(self) -> anyhow::Result<PathBuf> {
+        match self {
+            Game::Hsr => hsr::game_path(),
+            Game::Gi => gi::game_path(),
+            Game::Zzz => zzz::game_path(),
+        }
     }
 
     fn achievement_ids(self) -> anyhow::Result<Vec<u32>> {
@@ -122,11 +358,7 @@ impl Game {
             id: u32,
         }
 
-        let path = match self {
-            Game::Hsr => "/api/achievements",
-            Game::Gi => "/api/gi/achievements",
-            _ => unimplemented!(),
-        };
+        let path = &self.achievement_spec()?.api_path;
 
         let url = format!("https://stardb.gg{path}");
         let backup_url =
@@ -145,173 +377,206 @@ impl Game {
 
         Ok(achievement_ids)
     }
+}
 
-    #[cfg(feature = "pcap")]
-    fn devices(self) -> anyhow::Result<Vec<pcap::Device>> {
-        Ok(pcap::Device::list()?
-            .into_iter()
-            .filter(|d| d.flags.connection_status == pcap::ConnectionStatus::Connected)
-            .filter(|d| !d.addresses.is_empty())
-            .filter(|d| !d.flags.is_loopback())
-            .collect())
-    }
-
-    #[cfg(feature = "pcap")]
-    fn capture_device_pcap(
-        self,
-        i: usize,
-        device: pcap::Device,
-        device_tx: &mpsc::Sender<Vec<u8>>,
-        message_tx: &mpsc::Sender<Message>,
-    ) -> anyhow::Result<()> {
-        let packet_filer = match self {
-            Game::Hsr => "udp portrange 23301-23302",
-            Game::Gi => "udp portrange 22101-22102",
-            _ => unimplemented!(),
-        };
+/// Captures for every game with an achievement exporter at once and shows the results of
+/// whichever game turns out to be running. Uses the devices picked for detection.
+pub fn detect_achievements(
+    settings: &Settings,
+    message_tx: &mpsc::Sender<Message>,
+) -> CaptureSession {
+    let games = Game::ALL
+        .into_iter()
+        .filter(|game| game.supports_achievements())
+        .collect();
 
-        tracing::info!("Running exporter with pcap...");
-        tracing::debug!("Finding devices...");
+    capture(
+        games,
+        capture::live_sources(settings, settings.devices.get(None)),
+        Some(settings.clone()),
+        message_tx,
+    )
+}
 
-        loop {
-            let mut capture = pcap::Capture::from_device(device.clone())?
-                .immediate_mode(true)
-                .promisc(true)
-                .buffer_size(1024 * 1024 * 16) // 16MB
-                .open()?;
+/// Runs a sniffer for each game on the first source that starts. Live captures pass
+/// their settings to get recorded. The session stops once a sniffer is done, or once the
+/// user stops it if the settings keep watching.
+fn capture(
+    games: Vec<Game>,
+    sources: Vec<Box<dyn CaptureSource>>,
+    record: Option<Settings>,
+    message_tx: &mpsc::Sender<Message>,
+) -> CaptureSession {
+    let message_tx = message_tx.clone();
+    let session = CaptureSession::default();
+
+    {
+        let session = session.clone();
 
-            capture.filter(packet_filer, true)?;
+        thread::spawn(move || {
+            run_capture(&games, sources, record, &session, &message_tx);
+            session.stop();
+        });
+    }
 
-            message_tx
-                .send(Message::Toast({
-                    let mut toast = egui_notify::Toast::success(format!("Device {i} Ready~!"));
-                    toast.duration(None);
-                    toast
-                }))
-                .unwrap();
+    session
+}
 
-            message_tx
-                .send(Message::GoTo(State::Waiting("Running".to_string())))
-                .unwrap();
-            tracing::info!("Device {i} Ready~!");
+fn run_capture(
+    games: &[Game],
+    sources: Vec<Box<dyn CaptureSource>>,
+    record: Option<Settings>,
+    session: &CaptureSession,
+    message_tx: &mpsc::Sender<Message>,
+) {
+    // Detecting the game only makes sense with more than one to pick from
+    let detect = games.len() > 1;
+
+    let mut sniffers = Vec::new();
+    let mut error = None;
+
+    for &game in games {
+        let sniffer = game
+            .achievement_spec()
+            .and_then(|spec| Ok((game, spec.port_range, game.achievement_ids()?)));
+
+        match sniffer {
+            Ok(sniffer) => sniffers.push(sniffer),
+            // The other games can still be detected
+            Err(e) if detect => {
+                tracing::info!("Not detecting {}: {e}", game.name());
+                error = Some(e);
+            }
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
 
-            let mut pcapng = get_pcapng_path().and_then(|p| PcapngWriter::new(p).ok());
+    if sniffers.is_empty() || !detect && error.is_some() {
+        let e = error.unwrap_or_else(|| anyhow::anyhow!("No game to export achievements of"));
+        message_tx
+            .send(Message::GoTo(State::Error(e.to_string())))
+            .unwrap();
+        return;
+    }
 
-            let mut has_captured = false;
+    if session.is_stopped() {
+        return;
+    }
 
-            loop {
-                match capture.next_packet() {
-                    Ok(packet) => {
-                        device_tx.send(packet.data.to_vec())?;
-                        if let Some(ref mut writer) = pcapng {
-                            let ts = (packet.header.ts.tv_sec as u64 * 1_000_000_000)
-                                + (packet.header.ts.tv_usec as u64 * 1000);
-                            let _ = writer.write_packet(ts, packet.data);
-                        }
-                        has_captured = true;
-                    }
-                    Err(_) if !has_captured => break,
-                    Err(pcap::Error::TimeoutExpired) => continue,
-                    Err(e) => return Err(anyhow::anyhow!("{e}")),
-                }
-            }
+    run_sniffers(sniffers, detect, sources, record, session, message_tx);
+}
 
+/// Runs each game's sniffer, with its port range and achievement ids, on the first
+/// source that starts
+fn run_sniffers(
+    sniffers: Vec<(Game, (u16, u16), Vec<u32>)>,
+    detect: bool,
+    sources: Vec<Box<dyn CaptureSource>>,
+    record: Option<Settings>,
+    session: &CaptureSession,
+    message_tx: &mpsc::Sender<Message>,
+) {
+    let games: Vec<_> = sniffers.iter().map(|(game, _, _)| *game).collect();
+
+    // Replays end by themselves, only live captures are watched
+    let watchdog_seconds = record
+        .as_ref()
+        .map(|settings| settings.watchdog_seconds)
+        .unwrap_or_default();
+    let keep_watching = record
+        .as_ref()
+        .is_some_and(|settings| settings.keep_watching);
+
+    let port_ranges: Vec<_> = sniffers.iter().map(|(_, ports, _)| *ports).collect();
+    let (device_tx, device_rxs) =
+        capture::channels(&port_ranges, session.stats().clone(), message_tx.clone());
+    let recording = record.and_then(|settings| recording(&games, &port_ranges, &settings));
+    let context = capture::Context {
+        recording: recording.clone(),
+        port_ranges,
+        device_tx,
+        message_tx: message_tx.clone(),
+        session: session.clone(),
+    };
+
+    let source = match capture::start(sources, &context) {
+        Ok(source) => source,
+        Err(e) => {
             message_tx
-                .send(Message::Toast({
-                    let mut toast = egui_notify::Toast::error(format!(
-                        "Device {i} Error. Starting up again..."
-                    ));
-                    toast.duration(None);
-                    toast
-                }))
+                .send(Message::GoTo(State::Error(e.to_string())))
                 .unwrap();
-            tracing::info!("Device {i} Error. Starting up again...");
+            return;
         }
-    }
-
-    #[cfg(feature = "pktmon")]
-    fn capture_device_pktmon(
-        self,
-        device_tx: &mpsc::Sender<Vec<u8>>,
-        message_tx: &mpsc::Sender<Message>,
-    ) -> anyhow::Result<()> {
-        let port_range = match self {
-            Game::Hsr => (23301, 23302),
-            Game::Gi => (22101, 22102),
-            _ => unimplemented!(),
-        };
-        // let packet_filter = format!("udp portrange {}-{}", port_range.0, port_range.1);
-
-        tracing::info!("Running exporter with pktmon...");
+    };
 
-        loop {
-            let mut capture = pktmon::Capture::new()?;
+    if watchdog_seconds != 0 {
+        let game = (!detect).then_some(games[0]);
+        capture::watch(&context, watchdog_seconds, source, game);
+    }
 
-            vec![port_range.0, port_range.1]
-                .into_iter()
-                .map(|port| pktmon::filter::PktMonFilter {
-                    name: "UDP Filter".to_string(),
-                    transport_protocol: Some(pktmon::filter::TransportProtocol::UDP),
-                    port: port.into(),
-                    ..pktmon::filter::PktMonFilter::default()
+    // The sniffers stop once every source has dropped its sender
+    drop(context);
+
+    let exported = thread::scope(|scope| {
+        let sniffers: Vec<_> = sniffers
+            .iter()
+            .zip(device_rxs)
+            .map(|((game, _, achievement_ids), device_rx)| {
+                scope.spawn(move || {
+                    game.sniff(
+                        achievement_ids,
+                        |on_achievements| {
+                            game.sniff_packets(achievement_ids, &device_rx, on_achievements)
+                        },
+                        detect,
+                        keep_watching,
+                        session,
+                        message_tx,
+                    )
                 })
-                .for_each(|filter| {
-                    capture.add_filter(filter).unwrap();
-                });
+            })
+            .collect();
 
-            message_tx
-                .send(Message::Toast({
-                    let mut toast = egui_notify::Toast::success("Capture Ready~!".to_string());
-                    toast.duration(None);
-                    toast
-                }))
-                .unwrap();
+        // The scope still waits for the others
+        sniffers.into_iter().any(|sniffer| sniffer.join().unwrap())
+    });
 
-            message_tx
-                .send(Message::GoTo(State::Waiting("Running".to_string())))
-                .unwrap();
-            tracing::info!("Capture Ready~!");
-
-            let mut pcapng = get_pcapng_path()
-                .map(|p| PcapngWriter::new(p).ok())
-                .flatten();
-
-            let mut has_captured = false;
-            capture.start().unwrap();
-
-            loop {
-                match capture.next_packet_timeout(std::time::Duration::from_secs(1)) {
-                    Ok(packet) => {
-                        let payload = packet.payload.to_vec().clone();
-                        if let Some(ref mut writer) = pcapng {
-                            let ts = std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap()
-                                .as_nanos() as u64;
-                            let _ = writer.write_packet(ts, &payload);
-                        }
-                        device_tx.send(payload)?;
-                        has_captured = true;
-                    }
-                    Err(mpsc::RecvTimeoutError::Timeout) => {
-                        has_captured = true;
-                        continue;
-                    }
-                    Err(_) if !has_captured => break,
-                    Err(e) => return Err(anyhow::anyhow!("{e}")),
-                }
-            }
+    session.stats().log();
 
-            message_tx
-                .send(Message::Toast({
-                    let mut toast = egui_notify::Toast::error(
-                        "Capture Error. Starting up again...".to_string(),
-                    );
-                    toast.duration(None);
-                    toast
-                }))
-                .unwrap();
-            tracing::info!("Capture Error. Starting up again...");
+    // Failed and cancelled runs keep what the flight recorder has in memory
+    if !exported
+        && let Some(recording) = recording
+        && let Err(e) = recording.lock().unwrap().save()
+    {
+        tracing::info!("Couldn't save the flight recorder: {e}");
+    }
+}
+
+/// One recording per capture session, shared by all device threads
+fn recording(
+    games: &[Game],
+    port_ranges: &[(u16, u16)],
+    settings: &Settings,
+) -> Option<Arc<Mutex<Recording>>> {
+    let id = match games {
+        [game] => game.id(),
+        _ => "auto",
+    };
+    let comment = games
+        .iter()
+        .zip(port_ranges)
+        .map(|(game, (from, to))| format!("{} capture, udp ports {from}-{to}", game.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match pcapng::captures_dir().and_then(|dir| Recording::create(&dir, id, &comment, settings)) {
+        Ok(recording) => Some(Arc::new(Mutex::new(recording))),
+        Err(e) => {
+            tracing::info!("Couldn't create pcapng file: {e}");
+            None
         }
     }
 }
@@ -355,3 +620,148 @@ pub fn pulls_from_game_path(path: &Path) -> anyhow::Result<String> {
 
     Err(anyhow::anyhow!("Couldn't find pull url"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(number: usize, uid: Option<u32>) -> Account {
+        Account {
+            login: Login { number, uid },
+            name: String::new(),
+            achievements: Vec::new(),
+            unknown: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reconnects_continue_the_account_of_their_uid() {
+        let main = account(1, Some(800_000_040));
+
+        assert!(main.is_login(Login {
+            number: 3,
+            uid: Some(800_000_040)
+        }));
+        assert!(!main.is_login(Login {
+            number: 1,
+            uid: Some(700_000_001)
+        }));
+        assert!(!main.is_login(Login {
+            number: 1,
+            uid: None
+        }));
+        assert!(account(1, None).is_login(Login {
+            number: 1,
+            uid: None
+        }));
+        assert_eq!(region(800_000_040), "Asia");
+        assert_eq!(region(1_800_000_040), "Asia");
+    }
+
+    #[test]
+    fn updates_while_watching_change_the_results() {
+        use proto::fixtures::{message, varint};
+
+        let ids = [80001, 80002];
+        let list = [
+            message(4, &[(1, 80001), (2, 1), (3, 2), (5, 10)]),
+            message(4, &[(1, 80002), (2, 3), (3, 1), (5, 1)]),
+            message(4, &[(1, 90001), (2, 1)]),
+        ]
+        .concat();
+        let other = [varint(1, 80001), varint(2, 2)].concat();
+        let update = message(6, &[(1, 80001), (2, 2), (3, 10), (5, 10)]);
+
+        let (message_tx, message_rx) = mpsc::channel();
+        let session = CaptureSession::default();
+
+        let exported = Game::Hsr.sniff(
+            &ids,
+            |on_achievements| {
+                let mut decoder = decode::Decoder::new(&ids, Game::Hsr.achievement_spec()?);
+                let commands = [
+                    (1, list, Some(vec![(80001, 1), (80002, 3), (90001, 1)])),
+                    (2, other, None),
+                    (3, update, None),
+                ];
+
+                for (command_id, data, list) in commands {
+                    if let Some((login, achievements, unknown)) =
+                        decoder.command(command_id, &data, list)
+                        && !on_achievements(login, achievements, unknown)
+                    {
+                        break;
+                    }
+                }
+
+                decoder.finish()
+            },
+            false,
+            true,
+            &session,
+            &message_tx,
+        );
+        assert!(exported);
+        assert!(session.is_stopped());
+
+        let messages: Vec<_> = message_rx.try_iter().collect();
+        let [
+            Message::GoTo(State::Achievements(exported, 0)),
+            Message::Achievements(Game::Hsr, updated, 0),
+        ] = &messages[..]
+        else {
+            panic!("Expected the export and one update");
+        };
+
+        let progress = |accounts: &[Account]| -> Vec<_> {
+            accounts[0]
+                .achievements
+                .iter()
+                .map(|a| (a.id, a.status, a.current, a.total))
+                .collect()
+        };
+        assert_eq!(
+            progress(exported),
+            [(80001, 1, Some(2), Some(10)), (80002, 3, Some(1), Some(1))]
+        );
+        assert_eq!(
+            progress(updated),
+            [(80001, 2, Some(10), Some(10)), (80002, 3, Some(1), Some(1))]
+        );
+    }
+
+    #[test]
+    fn sniffers_run_on_a_memory_source() {
+        use crate::capture::{MemorySource, link::fixtures::frame};
+
+        let (message_tx, message_rx) = mpsc::channel();
+        let session = CaptureSession::default();
+        let memory = MemorySource::new(
+            "memory",
+            vec![("wifi".to_string(), 1)],
+            vec![
+                (0, 1, frame(23301, b"not encrypted")),
+                (0, 2, frame(53, b"dns")),
+                (0, 3, frame(23302, b"either")),
+            ],
+        );
+
+        run_sniffers(
+            vec![(Game::Hsr, (23301, 23302), vec![80001])],
+            false,
+            vec![Box::new(memory)],
+            None,
+            &session,
+            &message_tx,
+        );
+
+        // Both game packets got to the sniffer, which found nothing in them
+        let devices = session.stats().devices();
+        assert_eq!((devices[0].decoded(), devices[0].failed()), (0, 2));
+        let messages: Vec<_> = message_rx.try_iter().collect();
+        assert!(matches!(
+            &messages[..],
+            [Message::GoTo(State::Error(e))] if e == "No achievements found"
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+//! Just enough protobuf to pick single fields out of a command, the decoders only know
+//! the achievement packets
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// A field's value, as far as the wire format tells it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value<'a> {
+    Varint(u64),
+    /// Strings, bytes, embedded messages and packed repeated fields look all the same
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// The fields at the top level of `data` in order, or None if it isn't protobuf
+pub fn fields(mut data: &[u8]) -> Option<Vec<(u32, Value<'_>)>> {
+    let mut fields = Vec::new();
+
+    while !data.is_empty() {
+        let tag = varint(&mut data)?;
+        let value = match tag & 7 {
+            WIRE_VARINT => Value::Varint(varint(&mut data)?),
+            WIRE_FIXED64 => skip(&mut data, 8)?,
+            WIRE_LEN => {
+                let len = usize::try_from(varint(&mut data)?).ok()?;
+                let bytes = data.get(..len)?;
+                data = &data[len..];
+                Value::Bytes(bytes)
+            }
+            WIRE_FIXED32 => skip(&mut data, 4)?,
+            // Groups are long gone, anything else isn't protobuf
+            _ => return None,
+        };
+
+        let field = u32::try_from(tag >> 3).ok().filter(|&field| field != 0)?;
+        fields.push((field, value));
+    }
+
+    Some(fields)
+}
+
+/// The first varint with the field number `field` at the top level of `data`
+pub fn varint_field(data: &[u8], field: u32) -> Option<u64> {
+    fields(data)?
+        .into_iter()
+        .find_map(|(number, value)| match value {
+            Value::Varint(value) if number == field => Some(value),
+            _ => None,
+        })
+}
+
+fn varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+
+    for i in 0..10 {
+        let (byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn skip<'a>(data: &mut &[u8], len: usize) -> Option<Value<'a>> {
+    *data = data.get(len..)?;
+    Some(Value::Fixed)
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    fn encode(mut value: u64, data: &mut Vec<u8>) {
+        while value >= 0x80 {
+            data.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        data.push(value as u8);
+    }
+
+    pub fn varint(field: u32, value: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        encode((field as u64) << 3, &mut data);
+        encode(value, &mut data);
+        data
+    }
+
+    /// An embedded message of varints, leaving out the zeros like protobuf does
+    pub fn message(field: u32, fields: &[(u32, u64)]) -> Vec<u8> {
+        let data: Vec<u8> = fields
+            .iter()
+            .filter(|&&(_, value)| value != 0)
+            .flat_map(|&(field, value)| varint(field, value))
+            .collect();
+        bytes(field, &data)
+    }
+
+    pub fn bytes(field: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        encode((field as u64) << 3 | 2, &mut data);
+        encode(bytes.len() as u64, &mut data);
+        data.extend_from_slice(bytes);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_varints_between_other_fields() {
+        let data = [
+            0x0a, 0x02, b'h', b'i', // 1: "hi"
+            0x15, 1, 2, 3, 4, // 2: fixed32
+            0x18, 0xa8, 0x90, 0xbc, 0xfd, 0x02, // 3: 800_000_040
+            0x21, 1, 2, 3, 4, 5, 6, 7, 8, // 4: fixed64
+        ];
+
+        assert_eq!(varint_field(&data, 3), Some(800_000_040));
+        assert_eq!(varint_field(&data, 1), None);
+        assert_eq!(varint_field(&data, 5), None);
+        assert_eq!(varint_field(&data[..8], 3), None);
+    }
+
+    #[test]
+    fn lists_fields_in_order() {
+        let data = [
+            0x0a, 0x02, 0x08, 0x05, // 1: { 1: 5 }
+            0x0a, 0x00, // 1: {}
+            0x10, 0x96, 0x01, // 2: 150
+        ];
+
+        assert_eq!(
+            fields(&data),
+            Some(vec![
+                (1, Value::Bytes(&[0x08, 0x05])),
+                (1, Value::Bytes(&[])),
+                (2, Value::Varint(150)),
+            ])
+        );
+        assert_eq!(fields(&data[..3]), None);
+        assert_eq!(fields(&[0x03]), None);
+    }
+}
@@ -0,0 +1,252 @@
+use std::{path::PathBuf, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::Game;
+
+/// Optional overrides next to the app's storage, keyed by game id. Every field but the
+/// id can be replaced, e.g. `{"hsr": {"achievements": {"port_range": [23301, 23303]}}}`
+/// after a patch moved the servers, or `"achievements": null` to turn a feature off.
+const OVERRIDES_FILE: &str = "games.json";
+
+/// Everything about a game that isn't code
+#[derive(Serialize, Deserialize)]
+pub struct GameSpec {
+    pub id: String,
+    pub name: String,
+    /// Shown above the buttons on the game screen
+    pub note: Option<String>,
+    /// `None` for games without an achievement exporter
+    pub achievements: Option<AchievementSpec>,
+    pub pulls: PullSpec,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AchievementSpec {
+    /// Udp ports the game server talks on
+    pub port_range: (u16, u16),
+    /// Path of the achievement list on stardb and on the mirror
+    pub api_path: String,
+    /// Completed achievements of the logged in user
+    pub sync_url: String,
+    /// Key of the achievement list in the clipboard export
+    pub clipboard_key: String,
+    pub tracker_url: String,
+    /// Key file replacing the keys built into the app
+    pub keys: Option<PathBuf>,
+    /// Where the decoders find the player's uid, instead of searching the commands after
+    /// the login for it
+    pub login: Option<LoginSpec>,
+    /// Where the decoders find achievement updates while watching, instead of taking
+    /// every command with records like the achievement list's
+    pub updates: Option<UpdateSpec>,
+}
+
+/// The login response with the player's uid. Its command id and field number change
+/// with game patches, so they're only set in overrides.
+#[derive(Serialize, Deserialize)]
+pub struct LoginSpec {
+    pub command_id: u16,
+    /// Protobuf field number of the uid
+    pub uid_field: u32,
+}
+
+/// The notify sent when achievements make progress or are finished. Like the
+/// [`LoginSpec`], only set in overrides.
+#[derive(Serialize, Deserialize)]
+pub struct UpdateSpec {
+    pub command_id: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PullSpec {
+    pub label: String,
+    pub tracker_url: String,
+    pub import_url: String,
+    pub sync_url: String,
+}
+
+/// Indexed by `Game as usize`
+static SPECS: LazyLock<Vec<GameSpec>> = LazyLock::new(load);
+
+impl Game {
+    pub fn spec(self) -> &'static GameSpec {
+        &SPECS[self as usize]
+    }
+}
+
+/// Ports of every game's achievement exporter as built in, without the overrides
+pub fn builtin_port_ranges() -> Vec<(u16, u16)> {
+    defaults()
+        .into_iter()
+        .filter_map(|spec| spec.achievements)
+        .map(|achievements| achievements.port_range)
+        .collect()
+}
+
+fn defaults() -> Vec<GameSpec> {
+    vec![
+        GameSpec {
+            id: "hsr".to_string(),
+            name: "Honkai: Star Rail".to_string(),
+            note: None,
+            achievements: Some(AchievementSpec {
+                port_range: (23301, 23302),
+                api_path: "/api/achievements".to_string(),
+                sync_url: "https://stardb.gg/api/users/me/achievements/completed".to_string(),
+                clipboard_key: "hsr_achievements".to_string(),
+                tracker_url: "https://stardb.gg/achievement-tracker".to_string(),
+                keys: None,
+                login: None,
+                updates: None,
+            }),
+            pulls: PullSpec {
+                label: "Warp Exporter".to_string(),
+                tracker_url: "https://stardb.gg/warp-tracker".to_string(),
+                import_url: "https://stardb.gg/warp-import".to_string(),
+                sync_url: "https://stardb.gg/api/warps-import".to_string(),
+            },
+        },
+        GameSpec {
+            id: "gi".to_string(),
+            name: "Genshin Impact".to_string(),
+            note: Some(
+                "Make sure, that you fresh started the game before using the achievement exporter!!"
+                    .to_string(),
+            ),
+            achievements: Some(AchievementSpec {
+                port_range: (22101, 22102),
+                api_path: "/api/gi/achievements".to_string(),
+                sync_url: "https://stardb.gg/api/users/me/gi/achievements/completed".to_string(),
+                clipboard_key: "gi_achievements".to_string(),
+                tracker_url: "https://stardb.gg/genshin/achievement-tracker".to_string(),
+                keys: None,
+                login: None,
+                updates: None,
+            }),
+            pulls: PullSpec {
+                label: "Wish Exporter".to_string(),
+                tracker_url: "https://stardb.gg/genshin/wish-tracker".to_string(),
+                import_url: "https://stardb.gg/genshin/wish-import".to_string(),
+                sync_url: "https://stardb.gg/api/gi/wishes-import".to_string(),
+            },
+        },
+        GameSpec {
+            id: "zzz".to_string(),
+            name: "Zenless Zone Zero".to_string(),
+            note: None,
+            achievements: None,
+            pulls: PullSpec {
+                label: "Signal Exporter".to_string(),
+                tracker_url: "https://stardb.gg/zzz/signal-tracker".to_string(),
+                import_url: "https://stardb.gg/zzz/signal-import".to_string(),
+                sync_url: "https://stardb.gg/api/zzz/signals-import".to_string(),
+            },
+        },
+    ]
+}
+
+fn load() -> Vec<GameSpec> {
+    let overrides = read_overrides().unwrap_or_else(|e| {
+        tracing::info!("Couldn't read {OVERRIDES_FILE}: {e}");
+        serde_json::Value::Null
+    });
+
+    apply_overrides(defaults(), &overrides)
+}
+
+/// The specs with the overrides of their game merged in. The id is what the overrides
+/// are keyed by, so it stays.
+fn apply_overrides(specs: Vec<GameSpec>, overrides: &serde_json::Value) -> Vec<GameSpec> {
+    specs
+        .into_iter()
+        .map(|spec| {
+            let Some(patch) = overrides.get(&spec.id) else {
+                return spec;
+            };
+
+            let mut value = serde_json::to_value(&spec).unwrap();
+            merge(&mut value, patch);
+            value["id"] = spec.id.clone().into();
+
+            match serde_json::from_value::<GameSpec>(value) {
+                Ok(spec) => {
+                    tracing::info!("Using overrides from {OVERRIDES_FILE} for {}", spec.id);
+                    spec
+                }
+                Err(e) => {
+                    tracing::info!("Ignoring overrides for {}: {e}", spec.id);
+                    spec
+                }
+            }
+        })
+        .collect()
+}
+
+fn read_overrides() -> anyhow::Result<serde_json::Value> {
+    let mut path =
+        anyhow::Context::context(eframe::storage_dir(crate::APP_ID), "Storage dir not found")?;
+    path.push(OVERRIDES_FILE);
+
+    if !path.exists() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Objects are merged key by key, everything else is replaced
+fn merge(value: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (value, patch) {
+        (serde_json::Value::Object(value), serde_json::Value::Object(patch)) => {
+            for (key, patch) in patch {
+                match value.get_mut(key) {
+                    Some(value) => merge(value, patch),
+                    None => {
+                        value.insert(key.clone(), patch.clone());
+                    }
+                }
+            }
+        }
+        (value, patch) => *value = patch.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn objects_are_merged_and_everything_else_replaced() {
+        let mut value = json!({"a": {"b": 1, "c": [1, 2]}, "d": "kept"});
+        merge(&mut value, &json!({"a": {"c": [3], "e": null}, "f": true}));
+
+        assert_eq!(
+            value,
+            json!({"a": {"b": 1, "c": [3], "e": null}, "d": "kept", "f": true})
+        );
+    }
+
+    #[test]
+    fn overrides_replace_fields_but_not_ids() {
+        let overrides = json!({
+            "hsr": {"id": "gi", "achievements": {"port_range": [23301, 23303]}},
+            "gi": {"achievements": null},
+            "zzz": {"name": 5},
+        });
+
+        let specs = apply_overrides(defaults(), &overrides);
+        let ids: Vec<_> = specs.iter().map(|spec| spec.id.as_str()).collect();
+        assert_eq!(ids, ["hsr", "gi", "zzz"]);
+
+        let hsr = specs[0].achievements.as_ref().unwrap();
+        assert_eq!(hsr.port_range, (23301, 23303));
+        assert_eq!(hsr.api_path, "/api/achievements");
+
+        assert!(specs[1].achievements.is_none());
+        // Doesn't fit the spec, so it's ignored as a whole
+        assert_eq!(specs[2].name, "Zenless Zone Zero");
+    }
+}
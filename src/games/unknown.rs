@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use super::{Account, Achievement, Game};
+
+/// Finished achievements stardb didn't know yet, keyed by game id and uid. They're added
+/// to the account's export once stardb's list has them.
+const UNKNOWN_FILE: &str = "unknown_achievements.json";
+
+/// Adds this packet's unknown ids to the ones `account` reported before, in this run
+/// and, if its uid is known, in earlier ones kept at `path`. Kept ids that stardb knows
+/// by now are moved into its achievements. Without a `path` they're only kept for this
+/// run.
+pub fn reconcile(
+    game: Game,
+    achievement_ids: &[u32],
+    path: Option<&Path>,
+    account: &mut Account,
+    unknown: &[u32],
+) -> anyhow::Result<()> {
+    account.unknown.extend_from_slice(unknown);
+    account.unknown.sort_unstable();
+    account.unknown.dedup();
+
+    // Without a uid there's no telling whose they are in the next run
+    let (Some(uid), Some(path)) = (account.login.uid, path) else {
+        return Ok(());
+    };
+
+    let mut kept = read(path)?;
+
+    let ids = kept.entry(format!("{}/{uid}", game.id())).or_default();
+    ids.extend_from_slice(&account.unknown);
+    ids.sort_unstable();
+    ids.dedup();
+
+    let (known, still_unknown): (Vec<_>, Vec<_>) =
+        ids.iter().partition(|id| achievement_ids.contains(id));
+
+    for id in known {
+        if !account.achievements.iter().any(|a| a.id == id) {
+            tracing::info!("Achievement {id} is on stardb now");
+            account.achievements.push(Achievement {
+                id,
+                status: 2,
+                current: None,
+                total: None,
+                finished_at: None,
+            });
+        }
+    }
+
+    *ids = still_unknown.clone();
+    std::fs::write(path, serde_json::to_vec_pretty(&kept)?)?;
+
+    account.unknown = still_unknown;
+
+    Ok(())
+}
+
+fn read(path: &Path) -> anyhow::Result<BTreeMap<String, Vec<u32>>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+pub fn path() -> anyhow::Result<PathBuf> {
+    let mut path =
+        anyhow::Context::context(eframe::storage_dir(crate::APP_ID), "Storage dir not found")?;
+    path.push(UNKNOWN_FILE);
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Login;
+
+    #[test]
+    fn ids_stardb_learned_are_moved_into_the_achievements() {
+        let path = std::env::temp_dir().join(format!(
+            "stardb-exporter-unknown-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{ "hsr/800000001": [2, 9], "hsr/700000001": [3], "gi/800000001": [4] }"#,
+        )
+        .unwrap();
+
+        let mut account = Account {
+            login: Login {
+                number: 1,
+                uid: Some(800_000_001),
+            },
+            name: String::new(),
+            achievements: vec![Achievement {
+                id: 1,
+                status: 1,
+                current: None,
+                total: None,
+                finished_at: None,
+            }],
+            unknown: Vec::new(),
+        };
+
+        reconcile(Game::Hsr, &[1, 2, 3, 4], Some(&path), &mut account, &[5]).unwrap();
+
+        let ids: Vec<_> = account
+            .achievements
+            .iter()
+            .map(|a| (a.id, a.status))
+            .collect();
+        assert_eq!(ids, vec![(1, 1), (2, 2)]);
+        assert_eq!(account.unknown, vec![5, 9]);
+
+        // Other accounts and games keep theirs
+        let kept = read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(kept["hsr/800000001"], vec![5, 9]);
+        assert_eq!(kept["hsr/700000001"], vec![3]);
+        assert_eq!(kept["gi/800000001"], vec![4]);
+    }
+
+    #[test]
+    fn logins_without_a_uid_only_keep_them_for_this_run() {
+        let path = std::env::temp_dir().join(format!(
+            "stardb-exporter-unknown-none-{}.json",
+            std::process::id()
+        ));
+        let mut account = Account {
+            login: Login::default(),
+            name: String::new(),
+            achievements: Vec::new(),
+            unknown: vec![7],
+        };
+
+        reconcile(Game::Gi, &[1], Some(&path), &mut account, &[5, 7]).unwrap();
+
+        assert_eq!(account.unknown, vec![5, 7]);
+        assert!(!path.exists());
+    }
+}
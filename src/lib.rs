@@ -0,0 +1,8 @@
+pub mod app;
+pub mod capture;
+pub mod games;
+pub mod pcapng;
+pub mod themes;
+pub mod ui;
+
+pub const APP_ID: &str = "Stardb Exporter";
@@ -1,22 +1,29 @@
 #![windows_subsystem = "windows"]
 
-#[cfg(all(not(feature = "pcap"), feature = "pktmon"))]
-use std::env;
-mod app;
-mod games;
-mod pcapng;
-mod themes;
-mod ui;
+use std::{env, path::PathBuf};
 
-const APP_ID: &str = "Stardb Exporter";
+use stardb_exporter::{
+    APP_ID, app,
+    capture::{self, Backend},
+    games,
+};
 
 fn main() -> anyhow::Result<()> {
     let _guard = tracing_init()?;
 
-    #[cfg(all(not(feature = "pcap"), feature = "pktmon"))]
-    if !unsafe { windows::Win32::UI::Shell::IsUserAnAdmin().into() } {
+    let args = args()?;
+
+    let replay = args.replay.map(|path| (args.game, path));
+
+    // The helper captures for the GUI, so it asks right away. The GUI asks once it
+    // knows the backend.
+    if args.capture_helper.is_some() && Backend::default().needs_elevation() {
         tracing::info!("Asking for admin permissions...");
-        escalate_to_admin().expect("Error: failed to escalate privileges for pktmon version");
+        capture::restart_elevated().expect("Error: failed to escalate privileges for pktmon");
+    }
+
+    if let Some(address) = args.capture_helper {
+        return capture::run_helper(&address, args.helper_token.as_deref());
     }
 
     let native_options = eframe::NativeOptions {
@@ -32,7 +39,7 @@ fn main() -> anyhow::Result<()> {
     eframe::run_native(
         APP_ID,
         native_options,
-        Box::new(|cc| Ok(Box::new(app::App::new(cc)))),
+        Box::new(|cc| Ok(Box::new(app::App::new(cc, replay)))),
     )
     .map_err(|e| anyhow::anyhow!("{e}"))?;
 
@@ -56,46 +63,53 @@ fn tracing_init() -> anyhow::Result<tracing_appender::non_blocking::WorkerGuard>
     Ok(guard)
 }
 
-#[cfg(all(not(feature = "pcap"), feature = "pktmon"))]
-fn escalate_to_admin() -> Result<(), Box<dyn std::error::Error>> {
-    use std::os::windows::ffi::OsStrExt;
-    use windows::Win32::System::Console::GetConsoleWindow;
-    use windows::Win32::UI::Shell::{
-        SEE_MASK_NO_CONSOLE, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW,
-    };
-    use windows::Win32::UI::WindowsAndMessaging::{GW_OWNER, GetWindow, SW_SHOWNORMAL};
-    use windows::core::PCWSTR;
-    use windows::core::w;
-
-    let args_str = env::args().skip(1).collect::<Vec<_>>().join(" ");
-
-    let exe_path = env::current_exe()
-        .expect("Failed to get current exe")
-        .as_os_str()
-        .encode_wide()
-        .chain(Some(0))
-        .collect::<Vec<_>>();
-    let args = args_str.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
-
-    unsafe {
-        let mut options = SHELLEXECUTEINFOW {
-            cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
-            fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NO_CONSOLE,
-            hwnd: GetWindow(GetConsoleWindow(), GW_OWNER).unwrap_or(GetConsoleWindow()),
-            lpVerb: w!("runas"),
-            lpFile: PCWSTR(exe_path.as_ptr()),
-            lpParameters: PCWSTR(args.as_ptr()),
-            lpDirectory: PCWSTR::null(),
-            nShow: SW_SHOWNORMAL.0,
-            lpIDList: std::ptr::null_mut(),
-            lpClass: PCWSTR::null(),
-            dwHotKey: 0,
-            ..Default::default()
-        };
-
-        ShellExecuteExW(&mut options)?;
+struct Args {
+    game: games::Game,
+    replay: Option<PathBuf>,
+    /// Address to serve captures on instead of starting the GUI
+    capture_helper: Option<String>,
+    /// The app's token file, for a helper that runs as another user
+    helper_token: Option<PathBuf>,
+}
+
+fn args() -> anyhow::Result<Args> {
+    let mut parsed = Args {
+        game: games::Game::Hsr,
+        replay: None,
+        capture_helper: None,
+        helper_token: None,
     };
 
-    // Exit the current process since we launched a new elevated one
-    std::process::exit(0);
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => {
+                parsed.replay = Some(PathBuf::from(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("--replay needs a file"))?,
+                ))
+            }
+            "--capture-helper" => {
+                parsed.capture_helper = Some(
+                    args.next_if(|arg| !arg.starts_with("--"))
+                        .unwrap_or_else(|| capture::HELPER_ADDRESS.to_string()),
+                )
+            }
+            "--helper-token" => {
+                parsed.helper_token =
+                    Some(PathBuf::from(args.next().ok_or_else(|| {
+                        anyhow::anyhow!("--helper-token needs a file")
+                    })?))
+            }
+            "--game" => {
+                parsed.game = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--game needs one of hsr, gi"))?
+                    .parse()?
+            }
+            _ => {}
+        }
+    }
+
+    Ok(parsed)
 }
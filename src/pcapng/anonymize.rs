@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{Block, PcapngReader, PcapngWriter};
+use crate::capture::link::{self, ETHERTYPE_IPV4, ETHERTYPE_IPV6, IPPROTO_UDP};
+
+pub struct AnonymizeStats {
+    pub kept: usize,
+    pub dropped: usize,
+}
+
+/// Copies the game traffic from `input` to `output` with every MAC and IP address
+/// replaced by a made-up one. The same address always maps to the same replacement,
+/// so flows stay intact. Payloads are not touched.
+pub fn anonymize(
+    input: &Path,
+    output: &Path,
+    comment: &str,
+    port_range: (u16, u16),
+) -> std::io::Result<AnonymizeStats> {
+    let mut reader = PcapngReader::open(input)?;
+    let mut writer = PcapngWriter::new(output.to_path_buf(), comment)?;
+
+    let mut addresses = Addresses::default();
+    // Input interface to output interface, reset with every section
+    let mut interfaces = HashMap::new();
+
+    let mut stats = AnonymizeStats {
+        kept: 0,
+        dropped: 0,
+    };
+
+    while let Some(block) = reader.next_block()? {
+        let mut packet = match block {
+            Block::SectionHeader { .. } => {
+                interfaces.clear();
+                continue;
+            }
+            Block::EnhancedPacket(packet) | Block::SimplePacket(packet) => packet,
+            Block::InterfaceDescription(_) | Block::Unknown { .. } => continue,
+        };
+
+        let Some(link_type) = reader
+            .interfaces()
+            .get(packet.interface_id as usize)
+            .map(|i| i.link_type)
+        else {
+            stats.dropped += 1;
+            continue;
+        };
+
+        if !addresses.rewrite(link_type, &mut packet.data, port_range) {
+            stats.dropped += 1;
+            continue;
+        }
+
+        let interface_id = match interfaces.get(&packet.interface_id) {
+            Some(id) => *id,
+            None => {
+                // Interface names and descriptions can identify the machine
+                let name = format!("if{}", interfaces.len());
+                let id = writer.add_interface(link_type, &name, None)?;
+                interfaces.insert(packet.interface_id, id);
+                id
+            }
+        };
+
+        writer.write_packet(interface_id, packet.timestamp_ns, &packet.data)?;
+        stats.kept += 1;
+    }
+
+    Ok(stats)
+}
+
+#[derive(Default)]
+struct Addresses {
+    macs: HashMap<[u8; 6], [u8; 6]>,
+    ipv4: HashMap<[u8; 4], [u8; 4]>,
+    ipv6: HashMap<[u8; 16], [u8; 16]>,
+}
+
+impl Addresses {
+    /// Rewrites the addresses of a UDP packet in the port range. Returns `false` for
+    /// everything else, which is then dropped.
+    fn rewrite(&mut self, link_type: u16, data: &mut [u8], port_range: (u16, u16)) -> bool {
+        let Some((ethertype, ip)) = link::ip_packet(link_type, data) else {
+            return false;
+        };
+
+        let kept = match ethertype {
+            ETHERTYPE_IPV4 => self.rewrite_ipv4(&mut data[ip], port_range),
+            ETHERTYPE_IPV6 => self.rewrite_ipv6(&mut data[ip], port_range),
+            _ => false,
+        };
+        if !kept {
+            return false;
+        }
+
+        for offset in link::hardware_addresses(link_type, data) {
+            self.rewrite_mac(&mut data[offset..offset + 6]);
+        }
+        link::update_fcs(link_type, data);
+
+        true
+    }
+
+    fn rewrite_ipv4(&mut self, ip: &mut [u8], port_range: (u16, u16)) -> bool {
+        if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != IPPROTO_UDP {
+            return false;
+        }
+
+        // Later fragments have no udp header to check the ports against
+        let fragment_offset = u16::from_be_bytes([ip[6], ip[7]]) & 0x1fff;
+        let header_len = (ip[0] & 0x0f) as usize * 4;
+        if fragment_offset != 0 || header_len < 20 || ip.len() < header_len + 8 {
+            return false;
+        }
+
+        if !in_port_range(&ip[header_len..], port_range) {
+            return false;
+        }
+
+        let mut src: [u8; 4] = ip[12..16].try_into().unwrap();
+        let mut dst: [u8; 4] = ip[16..20].try_into().unwrap();
+        self.rewrite_ipv4_address(&mut src);
+        self.rewrite_ipv4_address(&mut dst);
+        ip[12..16].copy_from_slice(&src);
+        ip[16..20].copy_from_slice(&dst);
+
+        ip[10..12].copy_from_slice(&[0, 0]);
+        let checksum = !fold(sum(&ip[..header_len]));
+        ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+        let (header, udp) = ip.split_at_mut(header_len);
+
+        // A zero checksum means "not computed" for ipv4
+        if udp[6..8] != [0, 0] {
+            let udp_len = total_len.saturating_sub(header_len);
+
+            if udp_len < 8 || udp.len() < udp_len {
+                // Truncated, so the checksum can't be recomputed
+                udp[6..8].copy_from_slice(&[0, 0]);
+            } else {
+                let mut pseudo = Vec::with_capacity(12);
+                pseudo.extend_from_slice(&header[12..20]);
+                pseudo.extend_from_slice(&[0, IPPROTO_UDP]);
+                pseudo.extend_from_slice(&(udp_len as u16).to_be_bytes());
+
+                udp_checksum(&pseudo, &mut udp[..udp_len]);
+            }
+        }
+
+        true
+    }
+
+    fn rewrite_ipv6(&mut self, ip: &mut [u8], port_range: (u16, u16)) -> bool {
+        if ip.len() < 48 || ip[0] >> 4 != 6 || ip[6] != IPPROTO_UDP {
+            return false;
+        }
+
+        if !in_port_range(&ip[40..], port_range) {
+            return false;
+        }
+
+        let mut src: [u8; 16] = ip[8..24].try_into().unwrap();
+        let mut dst: [u8; 16] = ip[24..40].try_into().unwrap();
+        self.rewrite_ipv6_address(&mut src);
+        self.rewrite_ipv6_address(&mut dst);
+        ip[8..24].copy_from_slice(&src);
+        ip[24..40].copy_from_slice(&dst);
+
+        let udp_len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+        let (header, udp) = ip.split_at_mut(40);
+
+        if udp_len >= 8 && udp.len() >= udp_len {
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.extend_from_slice(&header[8..40]);
+            pseudo.extend_from_slice(&(udp_len as u32).to_be_bytes());
+            pseudo.extend_from_slice(&[0, 0, 0, IPPROTO_UDP]);
+
+            udp_checksum(&pseudo, &mut udp[..udp_len]);
+        }
+
+        true
+    }
+
+    /// Locally administered addresses, 02:00:00:00:00:01 and up
+    fn rewrite_mac(&mut self, mac: &mut [u8]) {
+        let original: [u8; 6] = mac.try_into().unwrap();
+
+        // Broadcast and multicast carry no information about the user
+        if original[0] & 1 == 1 {
+            return;
+        }
+
+        let next = self.macs.len() as u32 + 1;
+        let replacement = *self.macs.entry(original).or_insert_with(|| {
+            let n = next.to_be_bytes();
+            [0x02, 0, n[0], n[1], n[2], n[3]]
+        });
+        mac.copy_from_slice(&replacement);
+    }
+
+    /// Private addresses, 10.0.0.1 and up
+    fn rewrite_ipv4_address(&mut self, ip: &mut [u8; 4]) {
+        let next = self.ipv4.len() as u32 + 1;
+        *ip = *self.ipv4.entry(*ip).or_insert_with(|| {
+            let n = next.to_be_bytes();
+            [10, n[1], n[2], n[3]]
+        });
+    }
+
+    /// Unique local addresses, fd00::1 and up
+    fn rewrite_ipv6_address(&mut self, ip: &mut [u8; 16]) {
+        let next = self.ipv6.len() as u32 + 1;
+        *ip = *self.ipv6.entry(*ip).or_insert_with(|| {
+            let mut address = [0; 16];
+            address[0] = 0xfd;
+            address[12..].copy_from_slice(&next.to_be_bytes());
+            address
+        });
+    }
+}
+
+fn in_port_range(udp: &[u8], (from, to): (u16, u16)) -> bool {
+    let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+    let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+
+    (from..=to).contains(&src_port) || (from..=to).contains(&dst_port)
+}
+
+fn udp_checksum(pseudo_header: &[u8], udp: &mut [u8]) {
+    udp[6..8].copy_from_slice(&[0, 0]);
+
+    let checksum = match !fold(sum(pseudo_header) + sum(udp)) {
+        // Zero would mean "no checksum"
+        0 => 0xffff,
+        checksum => checksum,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+}
+
+fn sum(bytes: &[u8]) -> u32 {
+    bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)]) as u32)
+        .sum()
+}
+
+fn fold(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Radiotap header with a checksum, an 802.11 data frame and udp from 10.1.2.3 to
+    /// the game's port 22101
+    fn radiotap_frame() -> Vec<u8> {
+        let mut frame = vec![0, 0, 9, 0, 0b10, 0, 0, 0, 0x10];
+        frame.extend_from_slice(&[0x08, 0x01, 0, 0]);
+        frame.extend_from_slice(&[0x12; 6]);
+        frame.extend_from_slice(&[0x34; 6]);
+        frame.extend_from_slice(&[0x56; 6]);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&[0xaa, 0xaa, 0x03, 0, 0, 0, 0x08, 0x00]);
+        frame.extend_from_slice(&[0x45, 0, 0, 30, 0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0]);
+        frame.extend_from_slice(&[10, 1, 2, 3, 203, 0, 113, 7]);
+        frame.extend_from_slice(&[0xc3, 0x50, 0x56, 0x55, 0, 10, 0, 0, 1, 2]);
+        frame.extend_from_slice(&[0; 4]);
+        frame
+    }
+
+    #[test]
+    fn rewrites_radiotap_frames() {
+        let mut frame = radiotap_frame();
+        let mut addresses = Addresses::default();
+
+        assert!(addresses.rewrite(127, &mut frame, (22101, 22102)));
+
+        assert_eq!(frame[13..19], [0x02, 0, 0, 0, 0, 1]);
+        assert_eq!(frame[19..25], [0x02, 0, 0, 0, 0, 2]);
+        assert_eq!(frame[25..31], [0x02, 0, 0, 0, 0, 3]);
+        assert_eq!(frame[53..61], [10, 0, 0, 1, 10, 0, 0, 2]);
+
+        let end = frame.len() - 4;
+        let mut crc = flate2::Crc::new();
+        crc.update(&frame[9..end]);
+        assert_eq!(frame[end..], crc.sum().to_le_bytes());
+    }
+
+    #[test]
+    fn drops_other_ports() {
+        let mut frame = radiotap_frame();
+        let mut addresses = Addresses::default();
+
+        assert!(!addresses.rewrite(127, &mut frame, (23301, 23302)));
+    }
+}
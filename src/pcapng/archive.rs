@@ -0,0 +1,517 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::{Compression, write::GzEncoder};
+
+use super::PcapngWriter;
+use crate::app::Settings;
+
+/// A capture session stored as `<captures>/<timestamp>-<game>/part-<n>.pcapng`, with
+/// [`captures_dir`] as the captures directory outside of tests. Parts roll over once
+/// they reach the configured size.
+///
+/// As a flight recorder, only the most recent packets are kept in memory and nothing
+/// touches the disk unless the session is saved.
+pub struct Recording {
+    dir: PathBuf,
+    comment: String,
+    interfaces: Vec<(u16, String, Option<String>)>,
+    max_file_bytes: u64,
+    compress: bool,
+    part: u32,
+    path: PathBuf,
+    writer: Option<PcapngWriter>,
+    ring: Option<Ring>,
+}
+
+/// Interface, timestamp and data of the latest packets, up to `max_bytes` of data
+struct Ring {
+    packets: VecDeque<(u32, u64, Vec<u8>)>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Recording {
+    pub fn create(
+        captures: &Path,
+        game: &str,
+        comment: &str,
+        settings: &Settings,
+    ) -> std::io::Result<Self> {
+        let dir = captures.join(format!("{}-{game}", timestamp()));
+        let path = part_path(&dir, 1);
+
+        let mut recording = Recording {
+            dir,
+            comment: comment.to_string(),
+            interfaces: Vec::new(),
+            max_file_bytes: settings.max_capture_file_mb as u64 * 1024 * 1024,
+            compress: settings.compress_captures,
+            part: 1,
+            path,
+            writer: None,
+            ring: None,
+        };
+
+        if settings.flight_recorder {
+            tracing::info!(
+                "Keeping the last {} MB of the capture in memory",
+                settings.flight_recorder_mb
+            );
+            recording.ring = Some(Ring {
+                packets: VecDeque::new(),
+                bytes: 0,
+                max_bytes: settings.flight_recorder_mb as usize * 1024 * 1024,
+            });
+        } else {
+            recording.open()?;
+        }
+
+        Ok(recording)
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        // Sessions of the same game started within the same second get a suffix
+        // instead of overwriting each other's parts
+        let base = self.dir.as_os_str().to_owned();
+        let mut n = 1;
+        while let Err(e) = std::fs::create_dir(&self.dir) {
+            if e.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+
+            n += 1;
+            let mut dir = base.clone();
+            dir.push(format!("-{n}"));
+            self.dir = PathBuf::from(dir);
+        }
+        self.path = part_path(&self.dir, self.part);
+
+        self.writer = Some(PcapngWriter::new(self.path.clone(), &self.comment)?);
+
+        tracing::info!("Recording capture to {}", self.dir.display());
+
+        Ok(())
+    }
+
+    /// Writes what the flight recorder kept to disk. Does nothing for a recording
+    /// that's on disk already.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let Some(ring) = self.ring.take() else {
+            return Ok(());
+        };
+
+        self.open()?;
+        for (link_type, name, description) in &self.interfaces {
+            self.writer.as_mut().unwrap().add_interface(
+                *link_type,
+                name,
+                description.as_deref(),
+            )?;
+        }
+
+        tracing::info!("Saving the last {} captured packets", ring.packets.len());
+        for (interface_id, timestamp_ns, data) in ring.packets {
+            self.write_packet(interface_id, timestamp_ns, &data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_interface(
+        &mut self,
+        link_type: u16,
+        name: &str,
+        description: Option<&str>,
+    ) -> std::io::Result<u32> {
+        let interface_id = if self.ring.is_some() {
+            self.interfaces.len() as u32
+        } else {
+            self.writer()?.add_interface(link_type, name, description)?
+        };
+        self.interfaces
+            .push((link_type, name.to_string(), description.map(str::to_string)));
+        Ok(interface_id)
+    }
+
+    pub fn write_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp_ns: u64,
+        data: &[u8],
+    ) -> std::io::Result<()> {
+        if let Some(ring) = &mut self.ring {
+            ring.bytes += data.len();
+            ring.packets
+                .push_back((interface_id, timestamp_ns, data.to_vec()));
+
+            while ring.bytes > ring.max_bytes
+                && let Some((_, _, data)) = ring.packets.pop_front()
+            {
+                ring.bytes -= data.len();
+            }
+
+            return Ok(());
+        }
+
+        if self.max_file_bytes != 0
+            && self.writer()?.bytes_written() + data.len() as u64 > self.max_file_bytes
+        {
+            self.rotate()?;
+        }
+
+        self.writer()?
+            .write_packet(interface_id, timestamp_ns, data)
+    }
+
+    fn writer(&mut self) -> std::io::Result<&mut PcapngWriter> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| std::io::Error::other("Recording is closed"))
+    }
+
+    /// Closes the current part and continues in a new one with the same interfaces
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.finish_part();
+
+        self.part += 1;
+        self.path = part_path(&self.dir, self.part);
+
+        let mut writer = PcapngWriter::new(self.path.clone(), &self.comment)?;
+        for (link_type, name, description) in &self.interfaces {
+            writer.add_interface(*link_type, name, description.as_deref())?;
+        }
+        self.writer = Some(writer);
+
+        tracing::info!("Continuing capture in {}", self.path.display());
+
+        Ok(())
+    }
+
+    fn finish_part(&mut self) {
+        // Close the file before compressing it. A flight recorder that was never saved
+        // has no file.
+        if self.writer.take().is_none() || !self.compress {
+            return;
+        }
+
+        // The recording is locked by the capture threads while this runs. A part that
+        // didn't get compressed before the exporter quit is picked up by the next
+        // cleanup.
+        let path = self.path.clone();
+        thread::spawn(move || {
+            if let Err(e) = compress(&path) {
+                tracing::info!("Couldn't compress {}: {e}", path.display());
+            }
+        });
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.finish_part();
+    }
+}
+
+pub fn captures_dir() -> std::io::Result<PathBuf> {
+    let mut path = eframe::storage_dir(crate::APP_ID)
+        .ok_or_else(|| std::io::Error::other("Storage dir not found"))?;
+    path.push("log");
+    path.push("captures");
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+
+/// Applies the retention settings to older sessions in `captures`. Meant to run on
+/// startup.
+pub fn clean_captures(captures: &Path, settings: &Settings) -> std::io::Result<()> {
+    // Leave sessions alone that started while this was running
+    let started = timestamp();
+
+    let mut sessions: Vec<_> = captures
+        .read_dir()?
+        .flat_map(|r| r.ok().map(|d| d.path()))
+        .filter(|p| p.is_dir())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| n.to_string_lossy().as_ref() < started.as_str())
+        })
+        .collect();
+    // Session directories start with their timestamp
+    sessions.sort();
+
+    let mut sizes = Vec::new();
+    for session in &sessions {
+        let mut size = 0;
+
+        for part in session.read_dir()?.flat_map(|r| r.ok().map(|d| d.path())) {
+            // Parts left over from sessions that never closed properly
+            if settings.compress_captures
+                && part.extension().is_some_and(|e| e == "pcapng")
+                && let Err(e) = compress(&part)
+            {
+                tracing::info!("Couldn't compress {}: {e}", part.display());
+            }
+        }
+
+        for part in session.read_dir()?.flat_map(|r| r.ok()) {
+            size += part.metadata().map(|m| m.len()).unwrap_or_default();
+        }
+
+        sizes.push(size);
+    }
+
+    let max_bytes = settings.keep_captures_mb as u64 * 1024 * 1024;
+    let mut total: u64 = sizes.iter().sum();
+    let mut remaining = sessions.len();
+
+    for (session, size) in sessions.iter().zip(sizes) {
+        let too_many = settings.keep_captures != 0 && remaining > settings.keep_captures as usize;
+        let too_big = max_bytes != 0 && total > max_bytes;
+
+        if !too_many && !too_big {
+            break;
+        }
+
+        tracing::info!("Removing old capture session {}", session.display());
+        // A session that can't be removed, e.g. because it's open elsewhere, still
+        // counts, so one more of the others goes instead
+        if let Err(e) = std::fs::remove_dir_all(session) {
+            tracing::info!("Couldn't remove {}: {e}", session.display());
+            continue;
+        }
+
+        total -= size;
+        remaining -= 1;
+    }
+
+    Ok(())
+}
+
+fn part_path(dir: &Path, part: u32) -> PathBuf {
+    dir.join(format!("part-{part:03}.pcapng"))
+}
+
+fn compress(path: &Path) -> std::io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+
+    let mut input = BufReader::new(File::open(path)?);
+    let mut encoder = GzEncoder::new(File::create(&gz_path)?, Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+
+    std::fs::remove_file(path)
+}
+
+/// UTC `YYYYMMDD-HHMMSS`, so session directories sort chronologically
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::pcapng::{Packets, PcapngReader};
+
+    const KB: usize = 1024;
+
+    /// An empty captures directory of its own for each test
+    fn captures(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("stardb-exporter-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<_> = dir
+            .read_dir()
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// The only session in `captures`
+    fn session(captures: &Path) -> PathBuf {
+        let sessions = files(captures);
+        assert_eq!(sessions.len(), 1, "{sessions:?}");
+        captures.join(&sessions[0])
+    }
+
+    /// Devices and first byte of every packet in a part
+    fn packets(path: &Path) -> Vec<(usize, u8)> {
+        let mut packets = Packets::new(PcapngReader::open(path).unwrap());
+        std::iter::from_fn(|| packets.next_packet().unwrap())
+            .map(|(device, _, packet)| (device, packet.data[0]))
+            .collect()
+    }
+
+    #[test]
+    fn parts_roll_over_with_their_interfaces() {
+        let captures = captures("rollover");
+        let settings = Settings {
+            max_capture_file_mb: 1,
+            ..Settings::default()
+        };
+
+        let mut recording = Recording::create(&captures, "hsr", "test", &settings).unwrap();
+        let eth0 = recording.add_interface(1, "eth0", None).unwrap();
+        for i in 0..3 {
+            recording
+                .write_packet(eth0, i, &[i as u8; 600 * KB])
+                .unwrap();
+        }
+        drop(recording);
+
+        let session = session(&captures);
+        assert_eq!(
+            files(&session),
+            ["part-001.pcapng", "part-002.pcapng", "part-003.pcapng"]
+        );
+        assert_eq!(packets(&session.join("part-002.pcapng")), [(0, 1)]);
+
+        std::fs::remove_dir_all(captures).unwrap();
+    }
+
+    #[test]
+    fn finished_parts_are_compressed() {
+        let captures = captures("compress");
+        let settings = Settings {
+            max_capture_file_mb: 1,
+            compress_captures: true,
+            ..Settings::default()
+        };
+
+        let mut recording = Recording::create(&captures, "gi", "test", &settings).unwrap();
+        let eth0 = recording.add_interface(1, "eth0", None).unwrap();
+        for i in 0..2 {
+            recording
+                .write_packet(eth0, i, &[i as u8; 600 * KB])
+                .unwrap();
+        }
+        drop(recording);
+
+        // Compressed in the background
+        let session = session(&captures);
+        let compressed = ["part-001.pcapng.gz", "part-002.pcapng.gz"];
+        let started = Instant::now();
+        while files(&session) != compressed && started.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(files(&session), compressed);
+        assert_eq!(packets(&session.join(compressed[1])), [(0, 1)]);
+
+        std::fs::remove_dir_all(captures).unwrap();
+    }
+
+    #[test]
+    fn flight_recorder_keeps_the_latest_packets_until_saved() {
+        let captures = captures("flight-recorder");
+        let settings = Settings {
+            flight_recorder: true,
+            flight_recorder_mb: 1,
+            ..Settings::default()
+        };
+
+        let mut recording = Recording::create(&captures, "hsr", "test", &settings).unwrap();
+        let eth0 = recording.add_interface(1, "eth0", None).unwrap();
+        let wifi = recording.add_interface(105, "wifi", None).unwrap();
+        for i in 0..3 {
+            recording
+                .write_packet(eth0, i, &[i as u8; 400 * KB])
+                .unwrap();
+        }
+        recording.write_packet(wifi, 3, &[3; 100 * KB]).unwrap();
+        assert!(files(&captures).is_empty());
+
+        recording.save().unwrap();
+        drop(recording);
+
+        let session = session(&captures);
+        assert_eq!(files(&session), ["part-001.pcapng"]);
+        assert_eq!(
+            packets(&session.join("part-001.pcapng")),
+            [(0, 1), (0, 2), (1, 3)]
+        );
+
+        std::fs::remove_dir_all(captures).unwrap();
+    }
+
+    #[test]
+    fn old_sessions_are_removed_by_count_and_size() {
+        let captures = captures("retention");
+        for (session, size) in [
+            ("20240101-000000-hsr", 600),
+            ("20240102-000000-gi", 600),
+            ("20240103-000000-hsr", 300),
+            ("20240104-000000-hsr", 300),
+        ] {
+            let dir = captures.join(session);
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("part-001.pcapng.gz"), vec![0; size * KB]).unwrap();
+        }
+
+        let by_count = Settings {
+            keep_captures: 3,
+            keep_captures_mb: 0,
+            ..Settings::default()
+        };
+        clean_captures(&captures, &by_count).unwrap();
+        assert_eq!(
+            files(&captures),
+            [
+                "20240102-000000-gi",
+                "20240103-000000-hsr",
+                "20240104-000000-hsr"
+            ]
+        );
+
+        let by_size = Settings {
+            keep_captures: 0,
+            keep_captures_mb: 1,
+            ..Settings::default()
+        };
+        clean_captures(&captures, &by_size).unwrap();
+        assert_eq!(
+            files(&captures),
+            ["20240103-000000-hsr", "20240104-000000-hsr"]
+        );
+
+        std::fs::remove_dir_all(captures).unwrap();
+    }
+}
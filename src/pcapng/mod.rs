@@ -0,0 +1,16 @@
+mod anonymize;
+mod archive;
+mod reader;
+mod writer;
+
+pub use anonymize::anonymize;
+pub use archive::{Recording, captures_dir, clean_captures};
+pub use reader::{Block, Packets, PcapngReader};
+pub use writer::PcapngWriter;
+
+const SECTION_HEADER: u32 = 0x0a0d0d0a;
+const INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const SIMPLE_PACKET: u32 = 0x00000003;
+const ENHANCED_PACKET: u32 = 0x00000006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
@@ -0,0 +1,726 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+use super::{
+    BYTE_ORDER_MAGIC, ENHANCED_PACKET, INTERFACE_DESCRIPTION, SECTION_HEADER, SIMPLE_PACKET,
+};
+
+const PCAP_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_NANOS: u32 = 0xa1b23c4d;
+
+const OPT_END: u16 = 0;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+
+// Anything bigger is a corrupt length field, not a packet
+const MAX_BLOCK_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = bytes[..2].try_into().unwrap();
+        match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = bytes[..4].try_into().unwrap();
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
+enum Format {
+    Pcapng(Endian),
+    Pcap { endian: Endian, nanos: bool },
+}
+
+#[derive(Clone)]
+pub struct Interface {
+    pub link_type: u16,
+    pub snap_len: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Raw `if_tsresol` value. 6 (microseconds) unless the file says otherwise
+    pub ts_resolution: u8,
+}
+
+impl Interface {
+    /// The interface name, or a numbered one for interfaces without a name
+    pub fn name_or_numbered(&self, device: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("Interface {device}"))
+    }
+
+    fn timestamp_ns(&self, timestamp: u64) -> u64 {
+        let units_per_sec = if self.ts_resolution & 0x80 == 0 {
+            10u128
+                .checked_pow(self.ts_resolution as u32)
+                .unwrap_or(u128::MAX)
+        } else {
+            1u128 << (self.ts_resolution & 0x7f)
+        };
+
+        (timestamp as u128 * 1_000_000_000 / units_per_sec) as u64
+    }
+}
+
+pub struct Packet {
+    pub interface_id: u32,
+    pub timestamp_ns: u64,
+    pub original_len: u32,
+    pub data: Vec<u8>,
+}
+
+pub enum Block {
+    SectionHeader {
+        major_version: u16,
+        minor_version: u16,
+        options: Vec<(u16, Vec<u8>)>,
+    },
+    InterfaceDescription(Interface),
+    EnhancedPacket(Packet),
+    SimplePacket(Packet),
+    Unknown {
+        block_type: u32,
+        body: Vec<u8>,
+    },
+}
+
+/// Reads pcapng files (any byte order, any number of sections) as well as
+/// classic libpcap files, which show up as a single interface.
+pub struct PcapngReader<R> {
+    reader: R,
+    format: Format,
+    interfaces: Vec<Interface>,
+    pending: Option<Block>,
+}
+
+impl PcapngReader<Box<dyn Read + Send>> {
+    /// Opens a capture file, decompressing it first if it is gzipped
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let reader: Box<dyn Read + Send> = if file.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+            Box::new(BufReader::new(GzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+
+        PcapngReader::new(reader)
+    }
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if u32::from_le_bytes(magic) == SECTION_HEADER {
+            let mut reader = PcapngReader {
+                reader,
+                format: Format::Pcapng(Endian::Little),
+                interfaces: Vec::new(),
+                pending: None,
+            };
+            reader.pending = Some(reader.read_section_header()?);
+            return Ok(reader);
+        }
+
+        let (endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MICROS, _) => (Endian::Little, false),
+            (PCAP_NANOS, _) => (Endian::Little, true),
+            (_, PCAP_MICROS) => (Endian::Big, false),
+            (_, PCAP_NANOS) => (Endian::Big, true),
+            _ => return Err(invalid_data("Not a pcapng or pcap file")),
+        };
+
+        // version, thiszone, sigfigs, snaplen, network
+        let mut header = [0u8; 20];
+        reader.read_exact(&mut header)?;
+
+        let interface = Interface {
+            // The upper bits of the link type field carry FCS information
+            link_type: endian.u32(&header[16..20]) as u16,
+            snap_len: endian.u32(&header[12..16]),
+            name: None,
+            description: None,
+            ts_resolution: if nanos { 9 } else { 6 },
+        };
+
+        Ok(PcapngReader {
+            reader,
+            format: Format::Pcap { endian, nanos },
+            interfaces: vec![interface],
+            pending: None,
+        })
+    }
+
+    /// Interfaces of the current section
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    pub fn next_block(&mut self) -> std::io::Result<Option<Block>> {
+        if let Some(block) = self.pending.take() {
+            return Ok(Some(block));
+        }
+
+        let endian = match self.format {
+            Format::Pcapng(endian) => endian,
+            Format::Pcap { endian, nanos } => return self.read_pcap_record(endian, nanos),
+        };
+
+        let mut block_type = [0u8; 4];
+        if !self.read_start(&mut block_type)? {
+            return Ok(None);
+        }
+
+        // The section header type is a palindrome, so it reads the same in both byte orders
+        if u32::from_le_bytes(block_type) == SECTION_HEADER {
+            return self.read_section_header().map(Some);
+        }
+
+        let block_type = endian.u32(&block_type);
+
+        let mut total_len = [0u8; 4];
+        self.reader.read_exact(&mut total_len)?;
+        let total_len = endian.u32(&total_len) as usize;
+
+        let body = self.read_body(endian, total_len, 8)?;
+
+        let block = match block_type {
+            INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                let options = parse_options(endian, &body[8..]);
+
+                let interface = Interface {
+                    link_type: endian.u16(&body[0..2]),
+                    snap_len: endian.u32(&body[4..8]),
+                    name: string_option(&options, IF_NAME),
+                    description: string_option(&options, IF_DESCRIPTION),
+                    ts_resolution: options
+                        .iter()
+                        .find(|(code, value)| *code == IF_TSRESOL && !value.is_empty())
+                        .map(|(_, value)| value[0])
+                        .unwrap_or(6),
+                };
+
+                self.interfaces.push(interface.clone());
+                Block::InterfaceDescription(interface)
+            }
+            ENHANCED_PACKET if body.len() >= 20 => {
+                let interface_id = endian.u32(&body[0..4]);
+                let timestamp =
+                    ((endian.u32(&body[4..8]) as u64) << 32) | endian.u32(&body[8..12]) as u64;
+                let captured_len = endian.u32(&body[12..16]) as usize;
+
+                let Some(data) = body.get(20..20 + captured_len) else {
+                    return Err(invalid_data("Packet exceeds block length"));
+                };
+
+                let Some(interface) = self.interfaces.get(interface_id as usize) else {
+                    return Err(invalid_data(&format!("Unknown interface {interface_id}")));
+                };
+
+                Block::EnhancedPacket(Packet {
+                    interface_id,
+                    timestamp_ns: interface.timestamp_ns(timestamp),
+                    original_len: endian.u32(&body[16..20]),
+                    data: data.to_vec(),
+                })
+            }
+            SIMPLE_PACKET if body.len() >= 4 => {
+                let original_len = endian.u32(&body[0..4]);
+
+                let mut captured_len = (original_len as usize).min(body.len() - 4);
+                if let Some(interface) = self.interfaces.first()
+                    && interface.snap_len != 0
+                {
+                    captured_len = captured_len.min(interface.snap_len as usize);
+                }
+
+                Block::SimplePacket(Packet {
+                    interface_id: 0,
+                    timestamp_ns: 0,
+                    original_len,
+                    data: body[4..4 + captured_len].to_vec(),
+                })
+            }
+            INTERFACE_DESCRIPTION | ENHANCED_PACKET | SIMPLE_PACKET => {
+                return Err(invalid_data(&format!(
+                    "Block {block_type:#010x} is too short"
+                )));
+            }
+            _ => Block::Unknown { block_type, body },
+        };
+
+        Ok(Some(block))
+    }
+
+    /// Reads the rest of a section header after its block type
+    fn read_section_header(&mut self) -> std::io::Result<Block> {
+        let mut header = [0u8; 8];
+        self.reader.read_exact(&mut header)?;
+
+        let endian = if u32::from_le_bytes(header[4..8].try_into().unwrap()) == BYTE_ORDER_MAGIC {
+            Endian::Little
+        } else if u32::from_be_bytes(header[4..8].try_into().unwrap()) == BYTE_ORDER_MAGIC {
+            Endian::Big
+        } else {
+            return Err(invalid_data("Invalid byte order magic"));
+        };
+
+        let total_len = endian.u32(&header[0..4]) as usize;
+        let body = self.read_body(endian, total_len, 12)?;
+
+        if body.len() < 12 {
+            return Err(invalid_data("Section header is too short"));
+        }
+
+        self.format = Format::Pcapng(endian);
+        self.interfaces.clear();
+
+        Ok(Block::SectionHeader {
+            major_version: endian.u16(&body[0..2]),
+            minor_version: endian.u16(&body[2..4]),
+            options: parse_options(endian, &body[12..]),
+        })
+    }
+
+    /// Reads the remaining block body and checks the trailing length
+    fn read_body(
+        &mut self,
+        endian: Endian,
+        total_len: usize,
+        already_read: usize,
+    ) -> std::io::Result<Vec<u8>> {
+        if total_len < already_read + 4 || !total_len.is_multiple_of(4) || total_len > MAX_BLOCK_LEN
+        {
+            return Err(invalid_data(&format!("Invalid block length {total_len}")));
+        }
+
+        let mut body = vec![0u8; total_len - already_read];
+        self.reader.read_exact(&mut body)?;
+
+        let trailer = body.split_off(body.len() - 4);
+        if endian.u32(&trailer) as usize != total_len {
+            return Err(invalid_data("Block length mismatch"));
+        }
+
+        Ok(body)
+    }
+
+    fn read_pcap_record(&mut self, endian: Endian, nanos: bool) -> std::io::Result<Option<Block>> {
+        let mut header = [0u8; 16];
+        if !self.read_start(&mut header)? {
+            return Ok(None);
+        }
+
+        let ts_sec = endian.u32(&header[0..4]) as u64;
+        let ts_frac = endian.u32(&header[4..8]) as u64;
+        let captured_len = endian.u32(&header[8..12]) as usize;
+        let original_len = endian.u32(&header[12..16]);
+
+        if captured_len > MAX_BLOCK_LEN {
+            return Err(invalid_data(&format!(
+                "Invalid packet length {captured_len}"
+            )));
+        }
+
+        let mut data = vec![0u8; captured_len];
+        self.reader.read_exact(&mut data)?;
+
+        let timestamp_ns = ts_sec * 1_000_000_000 + if nanos { ts_frac } else { ts_frac * 1000 };
+
+        Ok(Some(Block::EnhancedPacket(Packet {
+            interface_id: 0,
+            timestamp_ns,
+            original_len,
+            data,
+        })))
+    }
+
+    /// Like `read_exact`, but a clean end of file before the first byte returns `false`
+    fn read_start(&mut self, buf: &mut [u8]) -> std::io::Result<bool> {
+        let mut read = 0;
+
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Walks the packets of a capture across all of its sections. Interface ids start over
+/// in every section, so packets come with a device that numbers the interfaces of all
+/// sections in order.
+pub struct Packets<R> {
+    reader: PcapngReader<R>,
+    devices: Vec<Interface>,
+    first_device: usize,
+}
+
+impl<R: Read> Packets<R> {
+    pub fn new(reader: PcapngReader<R>) -> Self {
+        // Legacy pcap files have their only interface in the file header
+        let devices = reader.interfaces().to_vec();
+
+        Self {
+            reader,
+            devices,
+            first_device: 0,
+        }
+    }
+
+    /// Interfaces of every section seen so far, indexed by device
+    pub fn devices(&self) -> &[Interface] {
+        &self.devices
+    }
+
+    /// The next packet with its device and link type
+    pub fn next_packet(&mut self) -> std::io::Result<Option<(usize, u16, Packet)>> {
+        while let Some(block) = self.reader.next_block()? {
+            match block {
+                Block::SectionHeader {
+                    major_version,
+                    minor_version,
+                    options,
+                } => {
+                    tracing::info!(
+                        "Section version {major_version}.{minor_version} with {} options",
+                        options.len()
+                    );
+                    self.first_device = self.devices.len();
+                }
+                Block::InterfaceDescription(interface) => {
+                    tracing::info!(
+                        "Interface {} ({}) with link type {}",
+                        interface.name.as_deref().unwrap_or("unnamed"),
+                        interface.description.as_deref().unwrap_or("no description"),
+                        interface.link_type
+                    );
+                    self.devices.push(interface);
+                }
+                Block::EnhancedPacket(packet) | Block::SimplePacket(packet) => {
+                    let device = self.first_device + packet.interface_id as usize;
+                    let Some(interface) = self.devices.get(device) else {
+                        return Err(invalid_data(&format!(
+                            "Packet on unknown interface {}",
+                            packet.interface_id
+                        )));
+                    };
+
+                    return Ok(Some((device, interface.link_type, packet)));
+                }
+                Block::Unknown { block_type, body } => {
+                    tracing::debug!("Skipping block {block_type:#010x} ({} bytes)", body.len());
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn parse_options(endian: Endian, mut bytes: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    let mut options = Vec::new();
+
+    while bytes.len() >= 4 {
+        let code = endian.u16(&bytes[0..2]);
+        let len = endian.u16(&bytes[2..4]) as usize;
+
+        if code == OPT_END {
+            break;
+        }
+
+        let Some(value) = bytes.get(4..4 + len) else {
+            break;
+        };
+        options.push((code, value.to_vec()));
+
+        bytes = bytes.get(4 + ((len + 3) & !3)..).unwrap_or_default();
+    }
+
+    options
+}
+
+fn string_option(options: &[(u16, Vec<u8>)], code: u16) -> Option<String> {
+    options
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcapng::PcapngWriter;
+
+    /// What `PcapngWriter` writes, as bytes
+    fn written(name: &str, write: impl FnOnce(&mut PcapngWriter)) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "stardb-exporter-{name}-{}.pcapng",
+            std::process::id()
+        ));
+
+        let mut writer = PcapngWriter::new(path.clone(), "test").unwrap();
+        write(&mut writer);
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    fn blocks(bytes: &[u8]) -> Vec<Block> {
+        let mut reader = PcapngReader::new(bytes).unwrap();
+        std::iter::from_fn(|| reader.next_block().unwrap()).collect()
+    }
+
+    fn little_endian_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let total_len = (12 + body.len()) as u32;
+
+        let mut block = block_type.to_le_bytes().to_vec();
+        block.extend_from_slice(&total_len.to_le_bytes());
+        block.extend_from_slice(body);
+        block.extend_from_slice(&total_len.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn reads_what_the_writer_wrote() {
+        let bytes = written("round-trip", |writer| {
+            let ethernet = writer.add_interface(1, "eth0", Some("Ethernet")).unwrap();
+            let sll = writer.add_interface(113, "any", None).unwrap();
+            writer
+                .write_packet(ethernet, 1_700_000_000_123_456_789, &[1, 2, 3, 4, 5])
+                .unwrap();
+            writer.write_packet(sll, 42, &[6; 64]).unwrap();
+        });
+
+        let blocks = blocks(&bytes);
+        assert_eq!(blocks.len(), 5);
+
+        let Block::SectionHeader {
+            major_version,
+            minor_version,
+            options,
+        } = &blocks[0]
+        else {
+            panic!("No section header");
+        };
+        assert_eq!((*major_version, *minor_version), (1, 0));
+        assert_eq!(string_option(options, 1).as_deref(), Some("test"));
+
+        let Block::InterfaceDescription(ethernet) = &blocks[1] else {
+            panic!("No interface");
+        };
+        assert_eq!(ethernet.link_type, 1);
+        assert_eq!(ethernet.name.as_deref(), Some("eth0"));
+        assert_eq!(ethernet.description.as_deref(), Some("Ethernet"));
+        assert_eq!(ethernet.ts_resolution, 9);
+
+        let Block::InterfaceDescription(sll) = &blocks[2] else {
+            panic!("No interface");
+        };
+        assert_eq!(sll.link_type, 113);
+        assert_eq!(sll.description, None);
+
+        let Block::EnhancedPacket(packet) = &blocks[3] else {
+            panic!("No packet");
+        };
+        assert_eq!(packet.interface_id, 0);
+        assert_eq!(packet.timestamp_ns, 1_700_000_000_123_456_789);
+        assert_eq!(packet.original_len, 5);
+        assert_eq!(packet.data, [1, 2, 3, 4, 5]);
+
+        let Block::EnhancedPacket(packet) = &blocks[4] else {
+            panic!("No packet");
+        };
+        assert_eq!(packet.interface_id, 1);
+        assert_eq!(packet.timestamp_ns, 42);
+        assert_eq!(packet.data, [6; 64]);
+    }
+
+    #[test]
+    fn interfaces_start_over_in_every_section() {
+        let mut bytes = written("first-section", |writer| {
+            let id = writer.add_interface(1, "eth0", None).unwrap();
+            writer.write_packet(id, 1, &[1]).unwrap();
+        });
+        bytes.extend(written("second-section", |writer| {
+            let id = writer.add_interface(101, "tun0", None).unwrap();
+            writer.write_packet(id, 2, &[2]).unwrap();
+        }));
+
+        let mut reader = PcapngReader::new(&bytes[..]).unwrap();
+        let mut packets = Vec::new();
+        while let Some(block) = reader.next_block().unwrap() {
+            if let Block::EnhancedPacket(packet) = block {
+                let link_type = reader.interfaces()[packet.interface_id as usize].link_type;
+                packets.push((link_type, packet.timestamp_ns, packet.data));
+            }
+        }
+
+        assert_eq!(packets, [(1, 1, vec![1]), (101, 2, vec![2])]);
+        assert_eq!(reader.interfaces().len(), 1);
+    }
+
+    #[test]
+    fn packets_number_devices_across_sections() {
+        let mut bytes = written("first-devices", |writer| {
+            writer.add_interface(1, "eth0", None).unwrap();
+            let id = writer.add_interface(113, "any", None).unwrap();
+            writer.write_packet(id, 1, &[1]).unwrap();
+        });
+        bytes.extend(written("second-devices", |writer| {
+            let id = writer.add_interface(101, "tun0", None).unwrap();
+            writer.write_packet(id, 2, &[2]).unwrap();
+        }));
+
+        let mut packets = Packets::new(PcapngReader::new(&bytes[..]).unwrap());
+        let mut seen = Vec::new();
+        while let Some((device, link_type, packet)) = packets.next_packet().unwrap() {
+            seen.push((device, link_type, packet.data));
+        }
+
+        assert_eq!(seen, [(1, 113, vec![1]), (2, 101, vec![2])]);
+        let names: Vec<_> = packets.devices().iter().map(|d| d.name.clone()).collect();
+        assert_eq!(
+            names,
+            [Some("eth0"), Some("any"), Some("tun0")].map(|n| n.map(String::from))
+        );
+    }
+
+    #[test]
+    fn reads_unknown_and_simple_packet_blocks() {
+        let mut bytes = written("simple-packets", |writer| {
+            writer.add_interface(1, "eth0", None).unwrap();
+        });
+        bytes.extend(little_endian_block(0x0000_0bad, &[1, 2, 3, 4]));
+        // Original length and the data padded to 32 bits
+        bytes.extend(little_endian_block(
+            SIMPLE_PACKET,
+            &[3, 0, 0, 0, 7, 8, 9, 0],
+        ));
+
+        let blocks = blocks(&bytes);
+        assert_eq!(blocks.len(), 4);
+
+        let Block::Unknown { block_type, body } = &blocks[2] else {
+            panic!("No unknown block");
+        };
+        assert_eq!(*block_type, 0x0000_0bad);
+        assert_eq!(body, &[1, 2, 3, 4]);
+
+        let Block::SimplePacket(packet) = &blocks[3] else {
+            panic!("No simple packet");
+        };
+        assert_eq!(packet.interface_id, 0);
+        assert_eq!(packet.original_len, 3);
+        assert_eq!(packet.data, [7, 8, 9]);
+    }
+
+    #[test]
+    fn reads_big_endian_sections() {
+        let mut body = BYTE_ORDER_MAGIC.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0, 1, 0, 0]);
+        body.extend_from_slice(&u64::MAX.to_be_bytes());
+
+        let big_endian_block = |block_type: u32, body: &[u8]| {
+            let total_len = (12 + body.len() as u32).to_be_bytes();
+            [&block_type.to_be_bytes()[..], &total_len, body, &total_len].concat()
+        };
+
+        let mut bytes = big_endian_block(SECTION_HEADER, &body);
+        bytes.extend(big_endian_block(
+            INTERFACE_DESCRIPTION,
+            &[0, 1, 0, 0, 0, 0, 0xff, 0xff],
+        ));
+        bytes.extend(big_endian_block(
+            ENHANCED_PACKET,
+            &[
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 2, 0, 0, 0, 2, 1, 2, 0, 0,
+            ],
+        ));
+
+        let blocks = blocks(&bytes);
+        let Block::EnhancedPacket(packet) = &blocks[2] else {
+            panic!("No packet");
+        };
+        // Microseconds without an if_tsresol option
+        assert_eq!(packet.timestamp_ns, 5_000);
+        assert_eq!(packet.data, [1, 2]);
+    }
+
+    fn pcap(magic: u32, big_endian: bool, ts_frac: u32) -> Vec<u8> {
+        let u32_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+
+        let mut bytes = u32_bytes(magic).to_vec();
+        // version 2.4, thiszone, sigfigs, snaplen and ethernet
+        bytes.extend_from_slice(&if big_endian {
+            [0, 2, 0, 4]
+        } else {
+            [2, 0, 4, 0]
+        });
+        for value in [0, 0, 65535, 1, 1_700_000_000, ts_frac, 3, 60] {
+            bytes.extend_from_slice(&u32_bytes(value));
+        }
+        bytes.extend_from_slice(&[1, 2, 3]);
+        bytes
+    }
+
+    #[test]
+    fn reads_pcap_files() {
+        for (magic, big_endian, ts_frac, timestamp_ns) in [
+            (PCAP_MICROS, false, 123_456, 1_700_000_000_123_456_000),
+            (PCAP_MICROS, true, 123_456, 1_700_000_000_123_456_000),
+            (PCAP_NANOS, false, 123_456_789, 1_700_000_000_123_456_789),
+            (PCAP_NANOS, true, 123_456_789, 1_700_000_000_123_456_789),
+        ] {
+            let bytes = pcap(magic, big_endian, ts_frac);
+            let mut reader = PcapngReader::new(&bytes[..]).unwrap();
+            assert_eq!(reader.interfaces()[0].link_type, 1);
+            assert_eq!(reader.interfaces()[0].snap_len, 65535);
+
+            let Some(Block::EnhancedPacket(packet)) = reader.next_block().unwrap() else {
+                panic!("No packet");
+            };
+            assert_eq!(packet.timestamp_ns, timestamp_ns);
+            assert_eq!(packet.original_len, 60);
+            assert_eq!(packet.data, [1, 2, 3]);
+            assert!(reader.next_block().unwrap().is_none());
+        }
+    }
+}
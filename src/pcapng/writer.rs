@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{BYTE_ORDER_MAGIC, ENHANCED_PACKET, INTERFACE_DESCRIPTION, SECTION_HEADER};
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+
+pub struct PcapngWriter {
+    file: File,
+    interface_count: u32,
+    packet_count: u32,
+    bytes_written: u64,
+}
+
+impl PcapngWriter {
+    /// Creates the file with a section header carrying `comment`. Interfaces
+    /// have to be added with `add_interface` before writing their packets.
+    pub fn new(path: PathBuf, comment: &str) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        let mut writer = PcapngWriter {
+            file,
+            interface_count: 0,
+            packet_count: 0,
+            bytes_written: 0,
+        };
+        writer.write_shb(comment)?;
+        Ok(writer)
+    }
+
+    fn write_shb(&mut self, comment: &str) -> std::io::Result<()> {
+        let mut body = Vec::new();
+
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1_u16.to_le_bytes());
+        body.extend_from_slice(&0_u16.to_le_bytes());
+        body.extend_from_slice(&0xffffffffffffffff_u64.to_le_bytes());
+
+        push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        push_option(
+            &mut body,
+            SHB_USERAPPL,
+            concat!("stardb-exporter ", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        push_option(&mut body, OPT_END, &[]);
+
+        self.write_block(SECTION_HEADER, &body)
+    }
+
+    /// Writes an interface description and returns the id to pass to `write_packet`
+    pub fn add_interface(
+        &mut self,
+        link_type: u16,
+        name: &str,
+        description: Option<&str>,
+    ) -> std::io::Result<u32> {
+        let mut body = Vec::new();
+
+        body.extend_from_slice(&link_type.to_le_bytes());
+        body.extend_from_slice(&0_u16.to_le_bytes());
+        body.extend_from_slice(&65536_u32.to_le_bytes());
+
+        push_option(&mut body, IF_NAME, name.as_bytes());
+        if let Some(description) = description {
+            push_option(&mut body, IF_DESCRIPTION, description.as_bytes());
+        }
+        // write_packet takes nanoseconds
+        push_option(&mut body, IF_TSRESOL, &[9]);
+        push_option(&mut body, OPT_END, &[]);
+
+        self.write_block(INTERFACE_DESCRIPTION, &body)?;
+
+        let interface_id = self.interface_count;
+        self.interface_count += 1;
+        Ok(interface_id)
+    }
+
+    pub fn write_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp_ns: u64,
+        data: &[u8],
+    ) -> std::io::Result<()> {
+        let mut body = Vec::new();
+
+        let ts_high: u32 = (timestamp_ns >> 32) as u32;
+        let ts_low: u32 = (timestamp_ns & 0xffffffff) as u32;
+        let captured_len: u32 = data.len() as u32;
+        let orig_len: u32 = data.len() as u32;
+
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&ts_high.to_le_bytes());
+        body.extend_from_slice(&ts_low.to_le_bytes());
+        body.extend_from_slice(&captured_len.to_le_bytes());
+        body.extend_from_slice(&orig_len.to_le_bytes());
+
+        body.extend_from_slice(data);
+        pad(&mut body);
+
+        push_option(&mut body, OPT_END, &[]);
+
+        self.write_block(ENHANCED_PACKET, &body)?;
+        self.packet_count += 1;
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+        let total_len = (4 + 4 + body.len() + 4) as u32;
+
+        let mut block = Vec::with_capacity(total_len as usize);
+        block.extend_from_slice(&block_type.to_le_bytes());
+        block.extend_from_slice(&total_len.to_le_bytes());
+        block.extend_from_slice(body);
+        block.extend_from_slice(&total_len.to_le_bytes());
+
+        self.file.write_all(&block)?;
+        self.bytes_written += block.len() as u64;
+        Ok(())
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad(body);
+}
+
+fn pad(body: &mut Vec<u8>) {
+    body.resize((body.len() + 3) & !3, 0);
+}
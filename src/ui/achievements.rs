@@ -1,29 +1,52 @@
-use std::thread;
+use std::{sync::mpsc, thread};
 
 use crate::{
     app::{App, Message, State},
-    games,
+    games::Account,
 };
 
-pub fn show(ui: &mut egui::Ui, achievements: &[u32], app: &App) {
-    let key = match app.game {
-        games::Game::Hsr => "hsr_achievements",
-        games::Game::Gi => "gi_achievements",
-        _ => unimplemented!(),
+pub fn show(ui: &mut egui::Ui, accounts: &[Account], selected: usize, app: &App) {
+    let Ok(spec) = app.game.achievement_spec() else {
+        return;
     };
+    let key = &spec.clipboard_key;
+
+    if accounts.len() > 1 {
+        ui.label("Logged in more than once during the capture, pick the login to export");
+
+        ui.horizontal(|ui| {
+            for (i, account) in accounts.iter().enumerate() {
+                if ui.selectable_label(i == selected, &account.name).clicked() {
+                    app.message_tx
+                        .send(Message::GoTo(State::Achievements(accounts.to_vec(), i)))
+                        .unwrap();
+                }
+            }
+        });
+
+        ui.separator();
+    }
+
+    let Some(account) = accounts.get(selected) else {
+        return;
+    };
+    let (achievements, unknown) = (&account.achievements, &account.unknown);
+    let finished = account.finished();
 
     ui.label("Finished");
 
     if ui
-        .button(format!(
-            "Copy {} achievements to clipboard",
-            achievements.len()
-        ))
+        .button(format!("Copy {} achievements to clipboard", finished.len()))
         .clicked()
     {
-        if let Err(e) = arboard::Clipboard::new()
-            .and_then(|mut c| c.set_text(serde_json::json!({ key: achievements }).to_string()))
-        {
+        // The records ride along next to the ids, so the import keeps working
+        let json = if app.settings.detailed_export {
+            serde_json::json!({ key: finished, "records": achievements })
+        } else {
+            serde_json::json!({ key: finished })
+        };
+
+        if let Err(e) = arboard::Clipboard::new().and_then(|mut c| c.set_text(json.to_string())) {
             app.message_tx
                 .send(Message::GoTo(State::Error(e.to_string())))
                 .unwrap();
@@ -36,105 +59,150 @@ pub fn show(ui: &mut egui::Ui, achievements: &[u32], app: &App) {
 
     ui.hyperlink_to("Click here to import", "https://stardb.gg/import");
 
+    if !unknown.is_empty() {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("{} achievements not yet on stardb", unknown.len()),
+        )
+        .on_hover_text(
+            "They're kept and added to the export once stardb knows them. Export again after its next update.",
+        );
+    }
+
     if let Some(user) = &app.user
         && ui
             .button(format!("Sync to account: \"{}\"", user.username))
             .clicked()
     {
-        app.message_tx
-            .send(Message::Toast(egui_notify::Toast::info("Syncing")))
-            .unwrap();
-
-        let prefix = match app.game {
-            games::Game::Hsr => "",
-            games::Game::Gi => "gi/",
-            _ => unimplemented!(),
+        sync(
+            spec.sync_url.clone(),
+            user.id.clone(),
+            finished.clone(),
+            &app.message_tx,
+        );
+    }
+
+    if let Some(session) = &app.capture
+        && !session.is_stopped()
+    {
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Watching for new achievements");
+            ui.add(egui::Spinner::new().color(ui.visuals().text_color()));
+        });
+
+        if ui.button("Stop watching").clicked() {
+            session.stop();
+        }
+    }
+
+    let unfinished: Vec<_> = achievements.iter().filter(|a| !a.is_finished()).collect();
+    if unfinished.is_empty() {
+        return;
+    }
+
+    ui.separator();
+
+    egui::CollapsingHeader::new(format!("{} in progress", unfinished.len())).show(ui, |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("in_progress").striped(true).show(ui, |ui| {
+                for achievement in unfinished {
+                    ui.label(achievement.id.to_string());
+                    match (achievement.current, achievement.total) {
+                        (Some(current), Some(total)) => ui.label(format!("{current}/{total}")),
+                        _ => ui.label("Unfinished"),
+                    };
+                    ui.end_row();
+                }
+            });
+        });
+    });
+}
+
+/// Replaces the account's achievements with `achievements`
+pub fn sync(url: String, id: String, achievements: Vec<u32>, message_tx: &mpsc::Sender<Message>) {
+    message_tx
+        .send(Message::Toast(egui_notify::Toast::info("Syncing")))
+        .unwrap();
+
+    let message_tx = message_tx.clone();
+
+    thread::spawn(move || {
+        let to_delete: Vec<i32> = match ureq::get(&url).header("Cookie", &id).call() {
+            Ok(r) => {
+                if r.status() == 200 {
+                    r.into_body().read_json().unwrap()
+                } else {
+                    message_tx
+                        .send(Message::Toast(egui_notify::Toast::error(
+                            "Error. Try Relogging",
+                        )))
+                        .unwrap();
+
+                    return;
+                }
+            }
+            Err(e) => {
+                message_tx
+                    .send(Message::Toast(egui_notify::Toast::error(format!(
+                        "Error: {e}"
+                    ))))
+                    .unwrap();
+                return;
+            }
         };
 
-        let url = format!("https://stardb.gg/api/users/me/{prefix}achievements/completed");
+        match ureq::delete(&url)
+            .header("Cookie", &id)
+            .force_send_body()
+            .send_json(to_delete)
+        {
+            Ok(r) => {
+                if r.status() != 200 {
+                    message_tx
+                        .send(Message::Toast(egui_notify::Toast::error(
+                            "Error. Try Relogging",
+                        )))
+                        .unwrap();
 
+                    return;
+                }
+            }
+            Err(e) => {
+                message_tx
+                    .send(Message::Toast(egui_notify::Toast::error(format!(
+                        "Error: {e}"
+                    ))))
+                    .unwrap();
+                return;
+            }
+        };
+
+        match ureq::put(&url)
+            .header("Cookie", &id)
+            .send_json(achievements)
         {
-            let message_tx = app.message_tx.clone();
-            let id = user.id.clone();
-            let achievements = achievements.to_vec();
-
-            thread::spawn(move || {
-                let to_delete: Vec<i32> = match ureq::get(&url).header("Cookie", &id).call() {
-                    Ok(r) => {
-                        if r.status() == 200 {
-                            r.into_body().read_json().unwrap()
-                        } else {
-                            message_tx
-                                .send(Message::Toast(egui_notify::Toast::error(
-                                    "Error. Try Relogging",
-                                )))
-                                .unwrap();
-
-                            return;
-                        }
-                    }
-                    Err(e) => {
-                        message_tx
-                            .send(Message::Toast(egui_notify::Toast::error(format!(
-                                "Error: {e}"
-                            ))))
-                            .unwrap();
-                        return;
-                    }
-                };
-
-                match ureq::delete(&url)
-                    .header("Cookie", &id)
-                    .force_send_body()
-                    .send_json(to_delete)
-                {
-                    Ok(r) => {
-                        if r.status() != 200 {
-                            message_tx
-                                .send(Message::Toast(egui_notify::Toast::error(
-                                    "Error. Try Relogging",
-                                )))
-                                .unwrap();
-
-                            return;
-                        }
-                    }
-                    Err(e) => {
-                        message_tx
-                            .send(Message::Toast(egui_notify::Toast::error(format!(
-                                "Error: {e}"
-                            ))))
-                            .unwrap();
-                        return;
-                    }
-                };
-
-                match ureq::put(&url)
-                    .header("Cookie", &id)
-                    .send_json(achievements)
-                {
-                    Ok(r) => {
-                        if r.status() == 200 {
-                            message_tx
-                                .send(Message::Toast(egui_notify::Toast::success("Synced")))
-                                .unwrap();
-                        } else {
-                            message_tx
-                                .send(Message::Toast(egui_notify::Toast::error(
-                                    "Error. Try Relogging",
-                                )))
-                                .unwrap();
-                        }
-                    }
-                    Err(e) => {
-                        message_tx
-                            .send(Message::Toast(egui_notify::Toast::error(format!(
-                                "Error: {e}"
-                            ))))
-                            .unwrap();
-                    }
+            Ok(r) => {
+                if r.status() == 200 {
+                    message_tx
+                        .send(Message::Toast(egui_notify::Toast::success("Synced")))
+                        .unwrap();
+                } else {
+                    message_tx
+                        .send(Message::Toast(egui_notify::Toast::error(
+                            "Error. Try Relogging",
+                        )))
+                        .unwrap();
                 }
-            });
+            }
+            Err(e) => {
+                message_tx
+                    .send(Message::Toast(egui_notify::Toast::error(format!(
+                        "Error: {e}"
+                    ))))
+                    .unwrap();
+            }
         }
-    }
+    });
 }
@@ -0,0 +1,104 @@
+use crate::{
+    app::{App, Message, State},
+    capture::{self, Backend, DeviceInfo},
+    games::Game,
+};
+
+/// Opens the device picker of `game`, or of game detection for `None`
+pub fn button(ui: &mut egui::Ui, app: &App, game: Option<Game>, label: &str) {
+    if !Backend::Pcap.is_available() {
+        return;
+    }
+
+    if ui.button(label).clicked() {
+        match capture::pcap_devices() {
+            Ok(devices) => app
+                .message_tx
+                .send(Message::GoTo(State::Devices(game, devices)))
+                .unwrap(),
+            Err(e) => app
+                .message_tx
+                .send(Message::Toast(egui_notify::Toast::error(format!("{e}"))))
+                .unwrap(),
+        }
+    }
+}
+
+pub fn show(ui: &mut egui::Ui, game: Option<Game>, devices: &[DeviceInfo], app: &App) {
+    let mut settings = app.settings.clone();
+    let selection = settings.devices.get_mut(game);
+    let mut changed = false;
+
+    if settings.backend != Backend::Pcap {
+        ui.small("These devices are only used by the pcap backend.");
+    }
+
+    let mut auto = selection.devices.is_none();
+    let auto_changed = ui
+        .horizontal(|ui| {
+            ui.radio_value(&mut auto, true, "Automatic").changed()
+                | ui.radio_value(&mut auto, false, "Selected devices")
+                    .changed()
+        })
+        .inner;
+
+    if auto_changed {
+        // Start from what the automatic selection picks
+        selection.devices = (!auto).then(|| {
+            devices
+                .iter()
+                .filter(|d| selection.uses(d))
+                .map(|d| d.name.clone())
+                .collect()
+        });
+        changed = true;
+    }
+
+    changed |= ui
+        .checkbox(&mut selection.include_loopback, "Include loopback devices")
+        .changed();
+
+    ui.separator();
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for device in devices {
+            let mut used = selection.uses(device);
+            let enabled = !auto && (selection.include_loopback || !device.loopback);
+
+            let label = device.description.as_deref().unwrap_or(&device.name);
+            if ui
+                .add_enabled(enabled, egui::Checkbox::new(&mut used, label))
+                .changed()
+                && let Some(names) = &mut selection.devices
+            {
+                if used {
+                    names.push(device.name.clone());
+                } else {
+                    names.retain(|n| n != &device.name);
+                }
+                changed = true;
+            }
+
+            ui.indent(&device.name, |ui| {
+                ui.small(&device.name);
+                if !device.addresses.is_empty() {
+                    ui.small(device.addresses.join(", "));
+                }
+                ui.small(device.flags.join(", "));
+            });
+        }
+    });
+
+    if ui.button("Done").clicked() {
+        let back = if game.is_some() {
+            State::Game
+        } else {
+            State::Menu
+        };
+        app.message_tx.send(Message::GoTo(back)).unwrap();
+    }
+
+    if changed {
+        app.message_tx.send(Message::Settings(settings)).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use egui_remixicon::icons;
+
+use crate::{
+    app::{App, Message, State},
+    capture::Diagnosis,
+    games,
+};
+
+pub fn show(ui: &mut egui::Ui, diagnosis: &Diagnosis, app: &App) {
+    ui.label(format!(
+        "No game traffic after {} seconds.",
+        diagnosis.seconds
+    ));
+
+    if !diagnosis.devices.is_empty() {
+        egui::Grid::new("diagnostics_devices")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Device");
+                ui.label("Frames");
+                ui.label("Udp on other ports");
+                ui.end_row();
+
+                for (name, frames, other_udp) in &diagnosis.devices {
+                    ui.label(name);
+                    ui.label(frames.to_string());
+                    ui.label(other_udp.to_string());
+                    ui.end_row();
+                }
+            });
+    }
+
+    ui.separator();
+
+    for hint in &diagnosis.hints {
+        ui.label(format!("{} {hint}", icons::INFORMATION_LINE));
+    }
+
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        if ui.button("Keep waiting").clicked() {
+            if let Some(session) = &app.capture {
+                session.keep_waiting();
+            }
+            app.message_tx
+                .send(Message::GoTo(State::Waiting("Running".to_string())))
+                .unwrap();
+        }
+
+        if ui.button("Restart capture").clicked() {
+            let session = match diagnosis.game {
+                Some(game) => game.achievements(&app.settings, &app.message_tx),
+                None => games::detect_achievements(&app.settings, &app.message_tx),
+            };
+            // Replaces and stops the current session
+            app.message_tx.send(Message::Capture(session)).unwrap();
+            app.message_tx
+                .send(Message::GoTo(State::Waiting("Preparing".to_string())))
+                .unwrap();
+        }
+
+        if ui.button("Stop").clicked() {
+            if let Some(session) = &app.capture {
+                session.stop();
+            }
+            app.message_tx.send(Message::GoTo(State::Game)).unwrap();
+        }
+    });
+}
@@ -2,41 +2,68 @@ use egui_remixicon::icons;
 
 use crate::{
     app::{App, Message, State},
-    games,
+    pcapng, ui,
 };
 
 pub fn show(ui: &mut egui::Ui, app: &App) {
-    match app.game {
-        games::Game::Hsr => {
-            if ui.button("Achievement Exporter").clicked() {
-                app.game.achievements(&app.message_tx);
-                app.message_tx
-                    .send(Message::GoTo(State::Waiting("Preparing".to_string())))
-                    .unwrap();
-            }
-
-            if ui.button("Warp Exporter").clicked() {
-                app.message_tx.send(Message::GoTo(State::PullMenu)).unwrap();
-            }
-        }
-        games::Game::Gi => {
-            ui.colored_label(ui.visuals().hyperlink_color, format!("{} Make sure, that you fresh started the game before using the achievement exporter!!", icons::INFORMATION_LINE));
-
-            if ui.button("Achievement Exporter").clicked() {
-                app.game.achievements(&app.message_tx);
-                app.message_tx
-                    .send(Message::GoTo(State::Waiting("Preparing".to_string())))
-                    .unwrap();
-            }
-
-            if ui.button("Wish Exporter").clicked() {
-                app.message_tx.send(Message::GoTo(State::PullMenu)).unwrap();
-            }
-        }
-        games::Game::Zzz => {
-            if ui.button("Signal Exporter").clicked() {
-                app.message_tx.send(Message::GoTo(State::PullMenu)).unwrap();
-            }
+    let spec = app.game.spec();
+
+    if let Some(note) = &spec.note {
+        ui.colored_label(
+            ui.visuals().hyperlink_color,
+            format!("{} {note}", icons::INFORMATION_LINE),
+        );
+    }
+
+    ui.add_enabled_ui(app.game.supports_achievements(), |ui| {
+        if ui
+            .button("Achievement Exporter")
+            .on_disabled_hover_text("Not supported for this game yet")
+            .clicked()
+        {
+            let session = app.game.achievements(&app.settings, &app.message_tx);
+            app.message_tx.send(Message::Capture(session)).unwrap();
+            app.message_tx
+                .send(Message::GoTo(State::Waiting("Preparing".to_string())))
+                .unwrap();
         }
+
+        ui::devices::button(ui, app, Some(app.game), "Capture devices");
+        replay_button(ui, app);
+        sanitize_button(ui, app);
+    });
+
+    if ui.button(&spec.pulls.label).clicked() {
+        app.message_tx.send(Message::GoTo(State::PullMenu)).unwrap();
+    }
+}
+
+fn replay_button(ui: &mut egui::Ui, app: &App) {
+    if ui.button("Replay capture file").clicked()
+        && let Some(path) = rfd::FileDialog::new()
+            .add_filter("Capture", &["pcapng", "pcap", "gz"])
+            .set_directory(pcapng::captures_dir().unwrap_or_default())
+            .pick_file()
+    {
+        let session = app.game.replay(path, &app.message_tx);
+        app.message_tx.send(Message::Capture(session)).unwrap();
+        app.message_tx
+            .send(Message::GoTo(State::Waiting("Preparing".to_string())))
+            .unwrap();
+    }
+}
+
+fn sanitize_button(ui: &mut egui::Ui, app: &App) {
+    if ui.button("Export sanitized capture").clicked()
+        && let Some(input) = rfd::FileDialog::new()
+            .add_filter("Capture", &["pcapng", "pcap", "gz"])
+            .set_directory(pcapng::captures_dir().unwrap_or_default())
+            .pick_file()
+        && let Some(output) = rfd::FileDialog::new()
+            .add_filter("Capture", &["pcapng"])
+            .set_file_name("sanitized.pcapng")
+            .save_file()
+    {
+        app.game.sanitize_capture(input, output, &app.message_tx);
     }
 }
@@ -2,7 +2,7 @@ use egui_remixicon::icons;
 
 use crate::{
     app::{App, Message, State},
-    games, themes,
+    themes,
 };
 
 pub fn show(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut App) {
@@ -11,14 +11,17 @@ pub fn show(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut App) {
 
         ui.add_space(32.0);
 
-        let waiting = matches!(app.state, State::Waiting(_));
+        let waiting = matches!(app.state, State::Waiting(_) | State::Diagnostics(_));
 
         let heading_text = match app.state {
-            State::Game | State::Achievements(_) | State::PullMenu => match app.game {
-                games::Game::Hsr => "Honkai Star Rail",
-                games::Game::Gi => "Genshin Impact",
-                games::Game::Zzz => "Zenless Zone Zero",
-            },
+            State::Game
+            | State::Achievements(..)
+            | State::PullMenu
+            | State::Devices(Some(_), _)
+            | State::Diagnostics(_) => {
+                app.game.name()
+            }
+            State::Settings => "Settings",
             _ => "Menu",
         };
 
@@ -91,8 +94,14 @@ pub fn show(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut App) {
 
                         if ui.button(website_job).clicked() {
                             let url = match app.state {
-                                State::Achievements(_) => app.game.achievement_url(),
-                                State::PullMenu | State::Pulls(_) => app.game.pull_url(),
+                                State::Achievements(..) => app
+                                    .game
+                                    .achievement_spec()
+                                    .map(|spec| spec.tracker_url.clone())
+                                    .unwrap_or("https://stardb.gg".to_string()),
+                                State::PullMenu | State::Pulls(_) => {
+                                    app.game.spec().pulls.tracker_url.clone()
+                                }
                                 _ => "https://stardb.gg".to_string(),
                             };
 
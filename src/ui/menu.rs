@@ -1,18 +1,32 @@
 use crate::{
-    app::{App, Message},
-    games::Game,
+    app::{App, Message, State},
+    games::{self, Game},
+    ui,
 };
 
 pub fn show(ui: &mut egui::Ui, app: &App) {
-    if ui.button("Honkai: Star Rail").clicked() {
-        app.message_tx.send(Message::Game(Game::Hsr)).unwrap();
+    for game in Game::ALL {
+        if ui.button(game.name()).clicked() {
+            app.message_tx.send(Message::Game(game)).unwrap();
+        }
     }
 
-    if ui.button("Genshin Impact").clicked() {
-        app.message_tx.send(Message::Game(Game::Gi)).unwrap();
+    if ui
+        .button("Detect game automatically")
+        .on_hover_text("Exports the achievements of whichever game you log into")
+        .clicked()
+    {
+        let session = games::detect_achievements(&app.settings, &app.message_tx);
+        app.message_tx.send(Message::Capture(session)).unwrap();
+        app.message_tx
+            .send(Message::GoTo(State::Waiting("Preparing".to_string())))
+            .unwrap();
     }
+    ui::devices::button(ui, app, None, "Capture devices for detection");
 
-    if ui.button("Zenless Zone Zero").clicked() {
-        app.message_tx.send(Message::Game(Game::Zzz)).unwrap();
+    ui.separator();
+
+    if ui.button("Settings").clicked() {
+        app.message_tx.send(Message::GoTo(State::Settings)).unwrap();
     }
 }
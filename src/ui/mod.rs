@@ -1,4 +1,6 @@
 pub mod achievements;
+pub mod devices;
+pub mod diagnostics;
 pub mod error;
 pub mod game;
 pub mod header;
@@ -6,4 +8,5 @@ pub mod login;
 pub mod menu;
 pub mod pull_menu;
 pub mod pulls;
+pub mod settings;
 pub mod waiting;
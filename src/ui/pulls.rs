@@ -1,7 +1,4 @@
-use crate::{
-    app::{App, Message, State},
-    games,
-};
+use crate::app::{App, Message, State};
 
 pub fn show(ui: &mut egui::Ui, url: &str, app: &App) {
     ui.label("Finished");
@@ -18,20 +15,12 @@ pub fn show(ui: &mut egui::Ui, url: &str, app: &App) {
         }
     }
 
-    let import_url = match app.game {
-        games::Game::Hsr => "https://stardb.gg/warp-import",
-        games::Game::Gi => "https://stardb.gg/genshin/wish-import",
-        games::Game::Zzz => "https://stardb.gg/zzz/signal-import",
-    };
+    let pulls = &app.game.spec().pulls;
 
-    ui.hyperlink_to("Click here to import", import_url);
+    ui.hyperlink_to("Click here to import", &pulls.import_url);
 
     if ui.button("Sync to stardb").clicked() {
-        let import_url = match app.game {
-            games::Game::Hsr => "https://stardb.gg/api/warps-import",
-            games::Game::Gi => "https://stardb.gg/api/gi/wishes-import",
-            games::Game::Zzz => "https://stardb.gg/api/zzz/signals-import",
-        };
+        let import_url = &pulls.sync_url;
 
         let request = if let Some(user) = &app.user {
             ureq::post(import_url).header("Cookie", &user.id)
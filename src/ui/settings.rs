@@ -0,0 +1,172 @@
+use crate::{
+    app::{App, Message},
+    capture::Backend,
+};
+
+pub fn show(ui: &mut egui::Ui, app: &App) {
+    let mut settings = app.settings.clone();
+    let mut changed = false;
+
+    let backends: Vec<_> = Backend::ALL
+        .into_iter()
+        .filter(|b| b.is_available())
+        .collect();
+
+    if backends.len() > 1 {
+        ui.label("Capture backend");
+
+        ui.horizontal(|ui| {
+            for backend in backends {
+                changed |= ui
+                    .selectable_value(&mut settings.backend, backend, backend.name())
+                    .changed();
+            }
+        });
+
+        if settings.backend.needs_elevation() {
+            ui.horizontal(|ui| {
+                ui.label("pktmon needs admin rights");
+                if ui.button("Restart as admin").clicked() {
+                    app.message_tx.send(Message::Elevate).unwrap();
+                }
+            });
+        }
+
+        if settings.backend == Backend::Stream {
+            ui.horizontal(|ui| {
+                ui.label("Read from");
+                changed |= ui.text_edit_singleline(&mut settings.stream).changed();
+            });
+            ui.small("tcp://<address>:<port> to listen, - for stdin or the path of a named pipe");
+        }
+
+        if settings.backend == Backend::Helper {
+            ui.horizontal(|ui| {
+                ui.label("Helper address");
+                changed |= ui.text_edit_singleline(&mut settings.helper).changed();
+            });
+            ui.small("Start it with stardb-exporter --capture-helper as admin/root");
+
+            ui.horizontal(|ui| {
+                ui.label("Helper captures with");
+                for backend in Backend::ALL
+                    .into_iter()
+                    .filter(|b| b.is_local() && b.is_available())
+                {
+                    changed |= ui
+                        .selectable_value(&mut settings.helper_backend, backend, backend.name())
+                        .changed();
+                }
+            });
+        }
+
+        ui.separator();
+    }
+
+    ui.label("Captures");
+
+    changed |= ui
+        .checkbox(
+            &mut settings.flight_recorder,
+            "Only save captures of failed or cancelled runs",
+        )
+        .changed();
+
+    if settings.flight_recorder {
+        ui.horizontal(|ui| {
+            ui.label("Keep the last");
+            changed |= ui
+                .add(egui::DragValue::new(&mut settings.flight_recorder_mb).suffix(" MB"))
+                .changed();
+            ui.label("in memory until then");
+        });
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Start a new file every");
+        changed |= ui
+            .add(egui::DragValue::new(&mut settings.max_capture_file_mb).suffix(" MB"))
+            .changed();
+    });
+
+    changed |= ui
+        .checkbox(
+            &mut settings.compress_captures,
+            "Compress finished capture files",
+        )
+        .changed();
+
+    ui.horizontal(|ui| {
+        ui.label("Keep the last");
+        changed |= ui
+            .add(egui::DragValue::new(&mut settings.keep_captures).suffix(" sessions"))
+            .changed();
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Keep at most");
+        changed |= ui
+            .add(egui::DragValue::new(&mut settings.keep_captures_mb).suffix(" MB"))
+            .changed();
+    });
+
+    ui.small("0 means no limit. Old sessions are removed when the app starts.");
+
+    ui.horizontal(|ui| {
+        ui.label("Show diagnostics after");
+        changed |= ui
+            .add(egui::DragValue::new(&mut settings.watchdog_seconds).suffix(" s"))
+            .changed();
+        ui.label("without game traffic");
+    });
+
+    if ui.button("Open capture folder").clicked() {
+        match crate::pcapng::captures_dir() {
+            Ok(dir) => {
+                if let Err(e) = open::that(dir) {
+                    app.message_tx
+                        .send(Message::Toast(egui_notify::Toast::error(format!("{e}"))))
+                        .unwrap();
+                }
+            }
+            Err(e) => app
+                .message_tx
+                .send(Message::Toast(egui_notify::Toast::error(format!("{e}"))))
+                .unwrap(),
+        }
+    }
+
+    ui.separator();
+
+    ui.label("Achievements");
+
+    changed |= ui
+        .checkbox(
+            &mut settings.detailed_export,
+            "Add status, progress and finish time to copied achievements",
+        )
+        .changed();
+
+    changed |= ui
+        .checkbox(
+            &mut settings.keep_watching,
+            "Keep watching for achievements finished while playing",
+        )
+        .changed();
+
+    if settings.keep_watching {
+        ui.add_enabled_ui(app.user.is_some(), |ui| {
+            changed |= ui
+                .checkbox(
+                    &mut settings.auto_sync,
+                    "Sync them to your account right away",
+                )
+                .on_disabled_hover_text("Log in first")
+                .changed();
+        });
+    }
+
+    if changed {
+        app.message_tx.send(Message::Settings(settings)).unwrap();
+    }
+}
@@ -1,6 +1,60 @@
+use std::sync::mpsc;
+
+use crate::{
+    app::{Message, State},
+    capture::{CaptureSession, CaptureStats},
+};
+
 pub fn show(ui: &mut egui::Ui, s: &str) {
     ui.horizontal(|ui| {
         ui.label(s);
         ui.add(egui::Spinner::new().color(ui.visuals().text_color()))
     });
 }
+
+pub fn stats(ui: &mut egui::Ui, stats: &CaptureStats) {
+    let devices = stats.devices();
+    if devices.is_empty() {
+        return;
+    }
+
+    egui::Grid::new("capture_stats")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Device");
+            ui.label("Frames");
+            ui.label("Bytes");
+            ui.label("Other udp");
+            ui.label("Game packets");
+            ui.label("Duplicates");
+            ui.label("Decoded");
+            ui.label("Not decoded");
+            ui.end_row();
+
+            for device in devices {
+                ui.label(&device.name);
+                ui.label(device.frames().to_string());
+                ui.label(device.bytes().to_string());
+                ui.label(device.other_udp().to_string());
+                ui.label(device.game_packets().to_string());
+                ui.label(device.duplicates().to_string());
+                ui.label(device.decoded().to_string());
+                ui.label(device.failed().to_string());
+                ui.end_row();
+            }
+        });
+
+    if stats.dropped() > 0 {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("{} packets dropped", stats.dropped()),
+        );
+    }
+}
+
+pub fn cancel(ui: &mut egui::Ui, session: &CaptureSession, message_tx: &mpsc::Sender<Message>) {
+    if ui.button("Cancel").clicked() {
+        session.stop();
+        message_tx.send(Message::GoTo(State::Game)).unwrap();
+    }
+}